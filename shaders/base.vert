@@ -2,8 +2,10 @@
 
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec2 aTex;
+layout (location = 2) in float aLight;
 
 out vec2 texCoord;
+out float light;
 
 uniform mat4 mvp;
 
@@ -11,4 +13,5 @@ void main()
 {
     gl_Position = mvp * vec4(aPos, 1.0);
     texCoord = aTex;
+    light = aLight;
 }
\ No newline at end of file
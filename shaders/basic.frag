@@ -1,6 +1,7 @@
 #version 330 core
 
 in vec2 texCoord;
+in float light;
 
 out vec4 FragColor;
 
@@ -14,5 +15,6 @@ void main()
     if (collision_test || texCoord.x <= 0.011 || texCoord.y <= 0.01) {
         collision = vec4(1.0,0.0,0.0,0.0);
     }
-    FragColor = texture(u_texture, texCoord) + collision;
+    vec4 color = texture(u_texture, texCoord);
+    FragColor = vec4(color.rgb * light, color.a) + collision;
 }
\ No newline at end of file
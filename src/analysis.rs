@@ -0,0 +1,478 @@
+use std::collections::{
+    BTreeSet, HashMap, HashSet, VecDeque,
+};
+
+use vek::{Aabb, Vec3};
+
+use crate::{
+    map::{Area, MarkerKind, Tile},
+    pathfind::{self, Agent},
+};
+
+/// Name of the trigger markers that end the level.
+pub const EXIT: &str = "exit";
+
+const SIDES: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Cells connected to each other, sorted floor by floor and
+/// row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub cells: Vec<Vec3<i32>>,
+}
+
+impl Region {
+    fn new(
+        cells: impl IntoIterator<Item = Vec3<i32>>,
+    ) -> Region {
+        let mut cells: Vec<Vec3<i32>> =
+            cells.into_iter().collect();
+        cells.sort_by_key(|c| (c.y, c.z, c.x));
+        Region { cells }
+    }
+
+    /// The box around every cell, `max` is exclusive.
+    pub fn bounds(&self) -> Aabb<i32> {
+        let mut bounds = Aabb {
+            min: self.cells[0],
+            max: self.cells[0] + 1,
+        };
+        for cell in &self.cells {
+            bounds.min = Vec3::partial_min(bounds.min, *cell);
+            bounds.max =
+                Vec3::partial_max(bounds.max, *cell + 1);
+        }
+        bounds
+    }
+}
+
+/// What `analyze` found out about an area.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The cell the player start stands on, `None` if there
+    /// is no start or nowhere to stand under it.
+    pub start: Option<Vec3<i32>>,
+    /// Walkable cells that can be reached from the start,
+    /// with how many steps away they are.
+    pub reachable: HashMap<Vec3<i32>, u32>,
+    /// Walkable places that can't be reached from the start.
+    pub unreachable: Vec<Region>,
+    /// Walkable places walled in on every side, there's no
+    /// way into them without breaking through.
+    pub sealed: Vec<Region>,
+    /// Tiles not resting on the bottom of the area, through
+    /// the tiles under or beside them.
+    pub floating: Vec<Vec3<i32>>,
+    /// Steps from the start to the closest exit tile or exit
+    /// trigger, `None` if there are no exits or none can be
+    /// reached.
+    pub exit_distance: Option<u32>,
+    /// Floors with walkable cells where none can be reached.
+    pub closed_floors: Vec<i32>,
+}
+
+impl Report {
+    /// Whether everything walkable can be reached.
+    pub fn is_connected(&self) -> bool {
+        self.start.is_some() && self.unreachable.is_empty()
+    }
+}
+
+/// Flood fills the walkable space from the player start to
+/// find the places the agent can't get to, jumping onto
+/// ledges as tall as its `jump_height`.
+pub fn analyze(area: &Area, agent: &Agent) -> Report {
+    // The roof can be walked on too, but it's only part of
+    // the level if it can be got to.
+    let bounds = area.bounds();
+    let on_roof = |cell: &Vec3<i32>| cell.y == bounds.max.y;
+    let roof = area
+        .points()
+        .take_while(|cell| cell.y == bounds.min.y)
+        .map(|cell| Vec3::new(cell.x, bounds.max.y, cell.z));
+    let walkable: HashMap<Vec3<i32>, f32> = area
+        .points()
+        .chain(roof)
+        .filter_map(|cell| {
+            Some((
+                cell,
+                pathfind::standing(area, agent, cell)?,
+            ))
+        })
+        .collect();
+    let links: HashMap<Vec3<i32>, Vec<Vec3<i32>>> = walkable
+        .iter()
+        .map(|(cell, feet)| {
+            let next = pathfind::neighbours(
+                area, agent, *cell, *feet,
+            )
+            .into_iter()
+            .map(|(next, _)| next)
+            .collect();
+            (*cell, next)
+        })
+        .collect();
+
+    let start = area.player_start().and_then(|marker| {
+        ground(area, agent, marker.position)
+    });
+    let mut reachable = HashMap::new();
+    if let Some(start) = start {
+        let mut open = VecDeque::new();
+        reachable.insert(start, 0);
+        open.push_back(start);
+        while let Some(cell) = open.pop_front() {
+            let steps = reachable[&cell] + 1;
+            for next in &links[&cell] {
+                if !reachable.contains_key(next) {
+                    reachable.insert(*next, steps);
+                    open.push_back(*next);
+                }
+            }
+        }
+    }
+
+    let unreachable = unreachable(&links, &reachable)
+        .into_iter()
+        .filter(|region| !region.cells.iter().all(on_roof))
+        .collect();
+    let sealed = sealed(area, &walkable, &reachable);
+
+    let exit_tiles = area
+        .filled()
+        .filter(|(_, tile)| *tile == Tile::Exit)
+        .map(|(cell, _)| cell);
+    let exit_distance = area
+        .markers()
+        .iter()
+        .filter(|marker| match &marker.kind {
+            MarkerKind::Trigger { name, .. } => name == EXIT,
+            _ => false,
+        })
+        .filter_map(|marker| {
+            ground(area, agent, marker.position)
+        })
+        .chain(exit_tiles)
+        .filter_map(|cell| reachable.get(&cell).copied())
+        .min();
+
+    let floors: BTreeSet<i32> = walkable
+        .keys()
+        .filter(|cell| !on_roof(cell))
+        .map(|cell| cell.y)
+        .collect();
+    let closed_floors = floors
+        .into_iter()
+        .filter(|y| {
+            !reachable.keys().any(|cell| cell.y == *y)
+        })
+        .collect();
+
+    Report {
+        start,
+        reachable,
+        unreachable,
+        sealed,
+        floating: floating(area),
+        exit_distance,
+        closed_floors,
+    }
+}
+
+/// The walkable cell at or under `position`, which can be
+/// on the roof or above the area.
+pub fn ground(
+    area: &Area,
+    agent: &Agent,
+    position: Vec3<f32>,
+) -> Option<Vec3<i32>> {
+    let mut cell = position.map(|e| e.round() as i32);
+    while cell.y >= area.bounds().min.y {
+        if pathfind::standing(area, agent, cell).is_some() {
+            return Some(cell);
+        }
+        cell.y -= 1;
+    }
+    None
+}
+
+/// Groups the cells that weren't reached by the ones they
+/// link to in either direction.
+fn unreachable(
+    links: &HashMap<Vec3<i32>, Vec<Vec3<i32>>>,
+    reachable: &HashMap<Vec3<i32>, u32>,
+) -> Vec<Region> {
+    let mut both_ways: HashMap<Vec3<i32>, Vec<Vec3<i32>>> =
+        HashMap::new();
+    for (cell, next) in links {
+        if reachable.contains_key(cell) {
+            continue;
+        }
+        both_ways.entry(*cell).or_default();
+        for next in next {
+            if !reachable.contains_key(next) {
+                both_ways
+                    .entry(*cell)
+                    .or_default()
+                    .push(*next);
+                both_ways
+                    .entry(*next)
+                    .or_default()
+                    .push(*cell);
+            }
+        }
+    }
+
+    let mut cells: Vec<Vec3<i32>> =
+        both_ways.keys().copied().collect();
+    cells.sort_by_key(|c| (c.y, c.z, c.x));
+    let mut seen = HashSet::new();
+    let mut regions = Vec::new();
+    for cell in cells {
+        if !seen.insert(cell) {
+            continue;
+        }
+        let mut region = vec![cell];
+        let mut open = vec![cell];
+        while let Some(cell) = open.pop() {
+            for next in &both_ways[&cell] {
+                if seen.insert(*next) {
+                    region.push(*next);
+                    open.push(*next);
+                }
+            }
+        }
+        regions.push(Region::new(region));
+    }
+    regions
+}
+
+/// The spaces between walls that have walkable cells but
+/// none reachable, and don't open out of the area.
+fn sealed(
+    area: &Area,
+    walkable: &HashMap<Vec3<i32>, f32>,
+    reachable: &HashMap<Vec3<i32>, u32>,
+) -> Vec<Region> {
+    let bounds = area.bounds();
+    let on_edge = |cell: Vec3<i32>| {
+        cell.x == bounds.min.x ||
+            cell.y == bounds.min.y ||
+            cell.z == bounds.min.z ||
+            cell.x == bounds.max.x - 1 ||
+            cell.y == bounds.max.y - 1 ||
+            cell.z == bounds.max.z - 1
+    };
+    let open = |cell: Vec3<i32>| {
+        area.tile_at(cell).map_or(false, |t| !t.is_opaque())
+    };
+
+    let mut seen = HashSet::new();
+    let mut regions = Vec::new();
+    for cell in area.points() {
+        if !open(cell) || !seen.insert(cell) {
+            continue;
+        }
+        let mut space = vec![cell];
+        let mut stack = vec![cell];
+        while let Some(cell) = stack.pop() {
+            for &(x, y, z) in &SIDES {
+                let next = cell + Vec3::new(x, y, z);
+                if open(next) && seen.insert(next) {
+                    space.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+
+        let cut_off = !space.iter().any(|cell| {
+            on_edge(*cell) || reachable.contains_key(cell)
+        });
+        let rooms: Vec<Vec3<i32>> = space
+            .into_iter()
+            .filter(|cell| walkable.contains_key(cell))
+            .collect();
+        if cut_off && !rooms.is_empty() {
+            regions.push(Region::new(rooms));
+        }
+    }
+    regions
+}
+
+/// Tiles that don't connect to the bottom floor through other
+/// tiles.
+fn floating(area: &Area) -> Vec<Vec3<i32>> {
+    let bottom = area.bounds().min.y;
+    let filled = |cell: Vec3<i32>| {
+        area.tile_at(cell).map_or(false, |t| t != Tile::Air)
+    };
+
+    let mut grounded = HashSet::new();
+    let mut stack: Vec<Vec3<i32>> = area
+        .points()
+        .take_while(|cell| cell.y == bottom)
+        .filter(|cell| filled(*cell))
+        .collect();
+    grounded.extend(stack.iter().copied());
+    while let Some(cell) = stack.pop() {
+        for &(x, y, z) in &SIDES {
+            let next = cell + Vec3::new(x, y, z);
+            if filled(next) && grounded.insert(next) {
+                stack.push(next);
+            }
+        }
+    }
+
+    area.points()
+        .filter(|cell| {
+            filled(*cell) && !grounded.contains(cell)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::Player,
+        generate::{self, Algorithm, Config},
+        map::Marker,
+    };
+
+    /// The player, without jumping.
+    fn walker() -> Agent {
+        Agent::new(0.5, 0.5, 0.5)
+    }
+
+    #[test]
+    fn generated_maps_are_connected() {
+        let configs = [
+            Config::default(),
+            Config {
+                floors: 3,
+                ..Config::default()
+            },
+            Config {
+                floors: 2,
+                algorithm: Algorithm::Caves {
+                    fill: 0.45,
+                    steps: 4,
+                },
+                ..Config::default()
+            },
+        ];
+        for config in &configs {
+            for seed in 0..20 {
+                let area =
+                    generate::generate(seed, config).unwrap();
+                let report = analyze(&area, &walker());
+                assert!(
+                    report.is_connected(),
+                    "seed {} {:?}: {:?}",
+                    seed,
+                    config.algorithm,
+                    report.unreachable
+                );
+                assert!(report.sealed.is_empty());
+                assert!(report.floating.is_empty());
+                assert!(report.closed_floors.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn walled_in_region() {
+        let area = Area::parse(
+            "size 7 5 3\n\
+             player 1 1 1\n\
+             trigger exit 3 1 1 1 1 1\n\
+             legend\n\
+             # wall\n\
+             . air\n\
+             layer\n\
+             #######\n\
+             #######\n\
+             #######\n\
+             #######\n\
+             #######\n\
+             layer\n\
+             #######\n\
+             #...#.#\n\
+             #####.#\n\
+             #######\n\
+             #######\n\
+             layer\n\
+             #######\n\
+             #######\n\
+             #######\n\
+             #######\n\
+             #######\n",
+        )
+        .unwrap();
+        let report = analyze(&area, &walker());
+
+        assert_eq!(report.start, Some(Vec3::new(1, 1, 1)));
+        assert_eq!(report.exit_distance, Some(2));
+        assert_eq!(report.unreachable.len(), 1);
+        assert_eq!(
+            report.unreachable[0].cells,
+            vec![Vec3::new(5, 1, 1), Vec3::new(5, 1, 2)]
+        );
+        assert_eq!(report.sealed, report.unreachable);
+        assert!(report.closed_floors.is_empty());
+    }
+
+    #[test]
+    fn ledges_need_a_jump() {
+        let mut area = Area::new(5, 3, 3);
+        for x in 0..5 {
+            area.set_tile(Vec3::new(x, 0, 1), Tile::Wall);
+        }
+        area.set_tile(Vec3::new(3, 1, 1), Tile::Wall);
+        area.add_marker(Marker::new(
+            MarkerKind::PlayerStart,
+            Vec3::new(1.0, 1.0, 1.0),
+        ));
+
+        let report = analyze(&area, &walker());
+        assert_eq!(report.unreachable.len(), 1);
+        assert_eq!(
+            report.unreachable[0].cells,
+            vec![Vec3::new(4, 1, 1), Vec3::new(3, 2, 1)]
+        );
+
+        let report = analyze(&area, &Player::agent());
+        assert!(report.is_connected());
+    }
+
+    #[test]
+    fn floating_tiles() {
+        let mut area = Area::new(3, 3, 3);
+        area.set_tile(Vec3::new(1, 0, 1), Tile::Wall);
+        area.set_tile(Vec3::new(1, 1, 1), Tile::Wall);
+        area.set_tile(Vec3::new(0, 2, 0), Tile::Wall);
+
+        let report = analyze(&area, &walker());
+        assert_eq!(report.floating, vec![Vec3::new(0, 2, 0)]);
+    }
+
+    #[test]
+    fn starts_on_the_roof() {
+        let area =
+            Area::load("assets/maps/debug.map").unwrap();
+        let report = analyze(&area, &Player::agent());
+        assert_eq!(report.start, Some(Vec3::new(2, 4, 2)));
+        // Dropping through the hole in the roof.
+        assert!(report
+            .reachable
+            .contains_key(&Vec3::new(5, 1, 1)));
+        assert!(report.is_connected());
+    }
+}
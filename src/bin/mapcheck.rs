@@ -0,0 +1,270 @@
+//! Checks map files without opening the game.
+//!
+//! ```text
+//! mapcheck assets/maps/debug.map assets/maps/other.area
+//! ```
+//!
+//! Prints every problem as `file:line:column: message` and
+//! exits with 1 if any map has errors.
+
+use std::{fmt, path::Path, process};
+
+use openglsdl::{
+    analysis,
+    component::Player,
+    map::{Area, MapError, MarkerKind, SourceMap, Tile},
+};
+
+enum Level {
+    Error,
+    Warning,
+}
+
+struct Problem {
+    level: Level,
+    at: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Problem {
+    fn error(
+        at: Option<(usize, usize)>,
+        message: String,
+    ) -> Problem {
+        Problem {
+            level: Level::Error,
+            at,
+            message,
+        }
+    }
+
+    fn warning(
+        at: Option<(usize, usize)>,
+        message: String,
+    ) -> Problem {
+        Problem {
+            level: Level::Warning,
+            at,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.level {
+            Level::Error => {
+                write!(f, "error: {}", self.message)
+            }
+            Level::Warning => {
+                write!(f, "warning: {}", self.message)
+            }
+        }
+    }
+}
+
+fn main() {
+    let paths: Vec<String> =
+        std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("usage: mapcheck <map>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let mut problems = check(Path::new(path));
+        problems
+            .sort_by_key(|p| p.at.unwrap_or((usize::MAX, 0)));
+        for problem in problems {
+            if let Level::Error = problem.level {
+                failed = true;
+            }
+            match problem.at {
+                Some((line, column)) => println!(
+                    "{}:{}:{}: {}",
+                    path, line, column, problem
+                ),
+                None => println!("{}: {}", path, problem),
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn check(path: &Path) -> Vec<Problem> {
+    let loaded =
+        if path.extension().map_or(false, |e| e == "map") {
+            std::fs::read_to_string(path)
+                .map_err(|e| MapError::Io(e.to_string()))
+                .and_then(|src| Area::parse_with_source(&src))
+        } else {
+            Area::load(path)
+                .map(|area| (area, SourceMap::default()))
+        };
+
+    match loaded {
+        Ok((area, source)) => lint(&area, &source),
+        Err(MapError::Parse {
+            line,
+            column,
+            message,
+        }) => vec![Problem::error(
+            Some((line, column)),
+            message,
+        )],
+        Err(e) => vec![Problem::error(None, e.to_string())],
+    }
+}
+
+fn lint(area: &Area, source: &SourceMap) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    let starts: Vec<usize> = area
+        .markers()
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.kind == MarkerKind::PlayerStart)
+        .map(|(i, _)| i)
+        .collect();
+    match starts.as_slice() {
+        [] => problems.push(Problem::error(
+            None,
+            "no player start".to_string(),
+        )),
+        [_] => {}
+        [_, others @ ..] => {
+            for i in others {
+                problems.push(Problem::warning(
+                    source.marker(*i),
+                    "player start declared again, only the \
+                     first one is used"
+                        .to_string(),
+                ));
+            }
+        }
+    }
+
+    let bounds = area.bounds();
+    let agent = Player::agent();
+    for (i, marker) in area.markers().iter().enumerate() {
+        let cell = marker.position.map(|e| e.round() as i32);
+        let inside = (bounds.min.x..bounds.max.x)
+            .contains(&cell.x) &&
+            (bounds.min.z..bounds.max.z).contains(&cell.z);
+        let at = format!(
+            "{}, {}, {}",
+            marker.position.x,
+            marker.position.y,
+            marker.position.z
+        );
+        if !inside {
+            problems.push(Problem::error(
+                source.marker(i),
+                format!("marker at {} is out of bounds", at),
+            ));
+            continue;
+        }
+
+        // Enemies and items drop down to whatever is under
+        // them, the player start is checked below.
+        let falls = match marker.kind {
+            MarkerKind::Enemy(_) | MarkerKind::Item(_) => {
+                true
+            }
+            _ => false,
+        };
+        if falls &&
+            analysis::ground(area, &agent, marker.position)
+                .is_none()
+        {
+            problems.push(Problem::error(
+                source.marker(i),
+                format!(
+                    "marker at {} has nothing to land on",
+                    at
+                ),
+            ));
+        }
+    }
+
+    let report = analysis::analyze(area, &agent);
+    if report.start.is_none() {
+        if let Some(i) = starts.first() {
+            problems.push(Problem::error(
+                source.marker(*i),
+                "nowhere to stand under the player start"
+                    .to_string(),
+            ));
+        }
+    }
+
+    if report.start.is_some() {
+        for region in &report.unreachable {
+            let walled_in = if report.sealed.contains(region)
+            {
+                ", they're walled in"
+            } else {
+                ""
+            };
+            problems.push(Problem::error(
+                source.tile(region.cells[0]),
+                format!(
+                    "{} walkable tiles can't be reached from \
+                     the player start{}",
+                    region.cells.len(),
+                    walled_in
+                ),
+            ));
+        }
+        for y in &report.closed_floors {
+            problems.push(Problem::error(
+                None,
+                format!(
+                    "nothing on layer {} can be reached",
+                    y - area.bounds().min.y + 1
+                ),
+            ));
+        }
+
+        let exits = area.markers().iter().enumerate().filter(
+            |(_, m)| match &m.kind {
+                MarkerKind::Trigger { name, .. } => {
+                    name == analysis::EXIT
+                }
+                _ => false,
+            },
+        );
+        if report.exit_distance.is_none() {
+            for (i, _) in exits {
+                problems.push(Problem::error(
+                    source.marker(i),
+                    "exit can't be reached".to_string(),
+                ));
+            }
+            for (point, tile) in area.iter() {
+                if tile == Tile::Exit {
+                    problems.push(Problem::error(
+                        source.tile(point),
+                        "exit can't be reached".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    for point in &report.floating {
+        problems.push(Problem::warning(
+            source.tile(*point),
+            format!(
+                "tile at {}, {}, {} is held up by nothing",
+                point.x, point.y, point.z
+            ),
+        ));
+    }
+
+    problems
+}
@@ -1,22 +1,37 @@
 use std::ops;
-use vek::{Quaternion, Vec3};
+use vek::{Aabb, Quaternion, Vec3};
 
-use crate::util::quat_from_ypr;
+use crate::{pathfind::Agent, util::quat_from_ypr};
 
 #[derive(Clone)]
 pub struct Position {
     vector: Vec3<f32>,
+    /// Where it was before the last simulation step.
+    previous: Vec3<f32>,
 }
 
 impl Position {
     pub fn new(x: f32, y: f32, z: f32) -> Position {
+        Position::from_vector(Vec3::new(x, y, z))
+    }
+
+    pub fn from_vector(vector: Vec3<f32>) -> Position {
         Position {
-            vector: Vec3::new(x, y, z),
+            vector,
+            previous: vector,
         }
     }
 
-    pub fn from_vector(vector: Vec3<f32>) -> Position {
-        Position { vector }
+    /// Keeps the current position as the previous one, called
+    /// at the start of every simulation step.
+    pub fn remember(&mut self) {
+        self.previous = self.vector;
+    }
+
+    /// Somewhere between the previous position and the
+    /// current one, `alpha` of the way there.
+    pub fn interpolated(&self, alpha: f32) -> Vec3<f32> {
+        Vec3::lerp(self.previous, self.vector, alpha)
     }
 
     pub fn add(&mut self, rh: Vec3<f32>) {
@@ -56,19 +71,44 @@ impl ops::Mul<f32> for Position {
 
 pub struct Rotation {
     pub quat: Quaternion<f32>,
+    /// What `quat` was before the last simulation step.
+    previous: Quaternion<f32>,
     yaw: f32,
     pitch: f32,
 }
 
 impl Rotation {
     pub fn new() -> Rotation {
+        Rotation::with_yaw(0.0)
+    }
+
+    /// Looking straight ahead, `yaw` radians counter
+    /// clockwise from north.
+    pub fn with_yaw(yaw: f32) -> Rotation {
+        let quat = quat_from_ypr(yaw, 0.0, 0.0).conjugate();
         Rotation {
-            quat: quat_from_ypr(0.0, 0.0, 0.0),
-            yaw: 0.0,
+            quat,
+            previous: quat,
+            yaw,
             pitch: 0.0,
         }
     }
 
+    /// Keeps the current rotation as the previous one, like
+    /// `Position::remember`.
+    pub fn remember(&mut self) {
+        self.previous = self.quat;
+    }
+
+    /// Somewhere between the previous rotation and the
+    /// current one, `alpha` of the way there.
+    pub fn interpolated(
+        &self,
+        alpha: f32,
+    ) -> Quaternion<f32> {
+        Quaternion::slerp(self.previous, self.quat, alpha)
+    }
+
     pub fn update_quat(&mut self, s: f32) {
         self.quat = Quaternion::slerp(
             self.quat,
@@ -90,14 +130,152 @@ impl Rotation {
     }
 }
 
+/// The entity the input controls and the camera follows.
+pub struct Player;
+
+/// How big the player is and how it gets around, shared with
+/// the tools that check maps for it.
+impl Player {
+    pub const BOUNDING_BOX: BoundingBox = BoundingBox {
+        width: 0.5,
+        height: 0.5,
+        eye: 0.35,
+    };
+    pub const STEPPING: Stepping = Stepping { height: 0.5 };
+    /// How high its jumps go when the key is held.
+    pub const JUMP_HEIGHT: f32 = 1.2;
+
+    /// Where the player can walk and jump to, for finding
+    /// out what it can reach. Falling doesn't hurt it, so it
+    /// drops down any height.
+    pub fn agent() -> Agent {
+        let BoundingBox { width, height, .. } =
+            Player::BOUNDING_BOX;
+        Agent {
+            max_drop: f32::INFINITY,
+            ..Agent::new(
+                width,
+                height,
+                Player::STEPPING.height,
+            )
+            .jumping(Player::JUMP_HEIGHT)
+        }
+    }
+}
+
+pub struct Enemy {
+    pub kind: String,
+}
+
+pub struct Item {
+    pub kind: String,
+}
+
+pub struct Light {
+    pub radius: f32,
+    pub color: Vec3<f32>,
+}
+
+/// A box around the entity's position that fires when
+/// something walks into it.
+pub struct Trigger {
+    pub name: String,
+    pub size: Vec3<f32>,
+}
+
+/// Anything spawned from the markers of the current level,
+/// despawned when leaving it.
+pub struct LevelEntity;
+
+/// The box an entity collides with the tiles with, centered
+/// on its position on x and z. Entities without one go
+/// through tiles.
 pub struct BoundingBox {
-    pub size: f32,
+    /// Size on both x and z.
+    pub width: f32,
     pub height: f32,
+    /// How far above the bottom of the box the position is,
+    /// where the eyes of the entity are.
+    pub eye: f32,
 }
 
 impl BoundingBox {
-    pub fn new(size: f32, height: f32) -> BoundingBox {
-        BoundingBox { size, height }
+    pub fn new(
+        width: f32,
+        height: f32,
+        eye: f32,
+    ) -> BoundingBox {
+        BoundingBox { width, height, eye }
+    }
+
+    /// The box around an entity at `position`.
+    pub fn at(&self, position: Vec3<f32>) -> Aabb<f32> {
+        let half = self.width * 0.5;
+        Aabb {
+            min: position - Vec3::new(half, self.eye, half),
+            max: position +
+                Vec3::new(
+                    half,
+                    self.height - self.eye,
+                    half,
+                ),
+        }
+    }
+}
+
+/// How tall a ledge an entity walks up without jumping, it
+/// also sticks to the ground walking down ledges as tall.
+pub struct Stepping {
+    pub height: f32,
+}
+
+/// Whether an entity is standing on something, from what it
+/// ran into the last time it moved.
+pub struct Grounded {
+    pub on_ground: bool,
+    /// Seconds since it last stood on something, infinite
+    /// until it first lands.
+    pub time_in_air: f32,
+}
+
+impl Grounded {
+    pub fn new() -> Grounded {
+        Grounded {
+            on_ground: false,
+            time_in_air: std::f32::INFINITY,
+        }
+    }
+}
+
+/// Lets an entity jump off the ground, see
+/// `plugin::jump`.
+pub struct Jump {
+    /// How high a jump goes if it isn't cut short.
+    pub height: f32,
+    /// Seconds after walking off a ledge it can still jump.
+    pub coyote_time: f32,
+    /// Seconds a jump pressed before landing is remembered.
+    pub buffer_time: f32,
+    /// What the upward velocity gets multiplied by when the
+    /// jump is let go early, making it lower.
+    pub release_cut: f32,
+    /// Seconds since a jump was pressed that hasn't happened
+    /// yet.
+    pub buffered: Option<f32>,
+    /// Going up from a jump that can still be cut short.
+    pub rising: bool,
+}
+
+impl Jump {
+    pub fn new(height: f32) -> Jump {
+        Jump {
+            height,
+            coyote_time: 0.1,
+            buffer_time: 0.15,
+            release_cut: 0.5,
+            buffered: None,
+            rising: false,
+        }
     }
 }
 
@@ -3,7 +3,9 @@ use std::ffi;
 use vek::Mat4;
 
 use crate::{
-    mesh::Mesh, shader::ShaderProgram, texture::Texture,
+    mesh::{Mesh, LIGHT_LOCATION},
+    shader::ShaderProgram,
+    texture::Texture,
 };
 
 pub struct Draw<'a> {
@@ -13,7 +15,7 @@ pub struct Draw<'a> {
 impl<'a> Draw<'a> {
     pub fn with(program: &'a ShaderProgram) -> Draw<'a> {
         program.enable();
-        Draw { program }
+        Draw { program }.with_light(1.0)
     }
 
     fn get_uniform_location(&self, name: &str) -> i32 {
@@ -68,6 +70,14 @@ impl<'a> Draw<'a> {
         self
     }
 
+    /// Brightness for meshes built without their own light.
+    pub fn with_light(self, light: f32) -> Draw<'a> {
+        unsafe {
+            gl::VertexAttrib1f(LIGHT_LOCATION, light);
+        }
+        self
+    }
+
     pub fn mesh(self, mesh: &Mesh) -> Draw<'a> {
         mesh.draw();
         self
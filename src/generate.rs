@@ -0,0 +1,761 @@
+use vek::{Vec2, Vec3};
+
+use crate::map::{Area, Facing, Marker, MarkerKind, Tile};
+
+/// SplitMix64, small and the same on every platform so a seed
+/// always gives back the same dungeon.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state =
+            self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30))
+            .wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27))
+            .wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `min..max`, `min` if the range is empty.
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        if max <= min {
+            return min;
+        }
+        min + (self.next_u64() % (max - min) as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f32) -> bool {
+        let bits = self.next_u64() >> 40;
+        (bits as f32 / (1u64 << 24) as f32) < probability
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    /// Rooms carved in the leaves of a binary space
+    /// partition and joined by corridors.
+    Rooms { min_room: usize },
+    /// Cellular automata caves, `fill` is how much of the
+    /// floor starts as rock and `steps` how many times
+    /// it's smoothed.
+    Caves { fill: f32, steps: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub width: usize,
+    pub depth: usize,
+    /// Floors are stacked on top of each other and joined by
+    /// stairwells.
+    pub floors: usize,
+    pub algorithm: Algorithm,
+    /// Enemies and items scattered around every floor.
+    pub enemies: usize,
+    pub items: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            width: 40,
+            depth: 30,
+            floors: 1,
+            algorithm: Algorithm::Rooms { min_room: 4 },
+            enemies: 4,
+            items: 2,
+        }
+    }
+}
+
+const MIN_SIZE: usize = 12;
+
+pub const ENEMY: &str = "grunt";
+pub const ITEM: &str = "health";
+
+/// Generates a dungeon, the same seed and config always give
+/// the same area.
+///
+/// Every floor takes two layers, a solid one to stand on and
+/// one with the rooms, plus a solid ceiling on top. The
+/// player starts on the ground floor, enemies and items are
+/// `ENEMY` and `ITEM` markers.
+pub fn generate(
+    seed: u64,
+    config: &Config,
+) -> Result<Area, String> {
+    if config.width < MIN_SIZE || config.depth < MIN_SIZE {
+        return Err(format!(
+            "dungeons must be at least {}x{}",
+            MIN_SIZE, MIN_SIZE
+        ));
+    }
+    if config.floors == 0 {
+        return Err(
+            "dungeons need at least one floor".to_string()
+        );
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut stairwells: Vec<Stairwell> = Vec::new();
+    for _ in 1..config.floors {
+        let mut stairwell = Stairwell::pick(&mut rng, config);
+        // Keep it away from the one arriving to the same
+        // floor, giving up after a while on maps too
+        // small for both.
+        for _ in 0..32 {
+            match stairwells.last() {
+                Some(below) if stairwell.overlaps(below) => {
+                    stairwell =
+                        Stairwell::pick(&mut rng, config)
+                }
+                _ => break,
+            }
+        }
+        stairwells.push(stairwell);
+    }
+
+    let mut floors = Vec::with_capacity(config.floors);
+    for floor in 0..config.floors {
+        let mut grid = match config.algorithm {
+            Algorithm::Rooms { min_room } => {
+                Grid::rooms(&mut rng, config, min_room)
+            }
+            Algorithm::Caves { fill, steps } => {
+                Grid::caves(&mut rng, config, fill, steps)
+            }
+        };
+        if floor > 0 {
+            stairwells[floor - 1].clear_arrival(&mut grid);
+        }
+        if let Some(stairwell) = stairwells.get(floor) {
+            stairwell.clear_departure(&mut grid);
+        }
+        grid.connect_regions();
+        floors.push(grid);
+    }
+
+    let mut area = Area::new(
+        config.width,
+        config.depth,
+        config.floors * 2 + 1,
+    );
+    for (floor, grid) in floors.iter().enumerate() {
+        let ground = floor as i32 * 2;
+        for z in 0..config.depth {
+            for x in 0..config.width {
+                let (x, z) = (x as i32, z as i32);
+                area.set_tile(
+                    Vec3::new(x, ground, z),
+                    Tile::Wall,
+                );
+                if !grid.is_open(x, z) {
+                    area.set_tile(
+                        Vec3::new(x, ground + 1, z),
+                        Tile::Wall,
+                    );
+                }
+            }
+        }
+    }
+    for z in 0..config.depth {
+        for x in 0..config.width {
+            area.set_tile(
+                Vec3::new(
+                    x as i32,
+                    config.floors as i32 * 2,
+                    z as i32,
+                ),
+                Tile::Wall,
+            );
+        }
+    }
+
+    for (floor, stairwell) in stairwells.iter().enumerate() {
+        stairwell.build(&mut area, floor as i32 * 2 + 1);
+    }
+
+    let mut candidates = Vec::new();
+    for (floor, grid) in floors.iter().enumerate() {
+        for z in 0..config.depth as i32 {
+            for x in 0..config.width as i32 {
+                let taken =
+                    stairwells.iter().any(|s| s.covers(x, z));
+                if grid.is_open(x, z) && !taken {
+                    candidates.push(Vec3::new(
+                        x as f32,
+                        floor as f32 * 2.0 + 1.0,
+                        z as f32,
+                    ));
+                }
+            }
+        }
+    }
+
+    let ground_floor = candidates
+        .iter()
+        .take_while(|position| position.y < 2.0)
+        .count();
+    if ground_floor == 0 {
+        return Err("no room left for the player".to_string());
+    }
+    let start = candidates.remove(rng.range(0, ground_floor));
+    area.add_marker(
+        Marker::new(MarkerKind::PlayerStart, start)
+            .facing(rng.range(0, 4) as f32 * 90.0),
+    );
+    for i in 0..config.enemies + config.items {
+        if candidates.is_empty() {
+            break;
+        }
+        let position =
+            candidates.remove(rng.range(0, candidates.len()));
+        let marker = if i < config.enemies {
+            Marker::new(
+                MarkerKind::Enemy(ENEMY.to_string()),
+                position,
+            )
+            .facing(rng.range(0, 4) as f32 * 90.0)
+        } else {
+            Marker::new(
+                MarkerKind::Item(ITEM.to_string()),
+                position,
+            )
+        };
+        area.add_marker(marker);
+    }
+
+    Ok(area)
+}
+
+/// One floor seen from above, `true` where it's open.
+struct Grid {
+    width: usize,
+    depth: usize,
+    open: Vec<bool>,
+}
+
+impl Grid {
+    fn new(width: usize, depth: usize) -> Grid {
+        Grid {
+            width,
+            depth,
+            open: vec![false; width * depth],
+        }
+    }
+
+    fn index(&self, x: i32, z: i32) -> Option<usize> {
+        // The outer ring is always rock.
+        if x < 1 ||
+            z < 1 ||
+            x >= self.width as i32 - 1 ||
+            z >= self.depth as i32 - 1
+        {
+            return None;
+        }
+        Some(x as usize + z as usize * self.width)
+    }
+
+    fn is_open(&self, x: i32, z: i32) -> bool {
+        self.index(x, z).map_or(false, |i| self.open[i])
+    }
+
+    fn carve(&mut self, x: i32, z: i32) {
+        if let Some(i) = self.index(x, z) {
+            self.open[i] = true;
+        }
+    }
+
+    fn carve_rect(&mut self, min: Vec2<i32>, max: Vec2<i32>) {
+        for z in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.carve(x, z);
+            }
+        }
+    }
+
+    /// An L shaped corridor, first along x then along z.
+    fn carve_corridor(
+        &mut self,
+        from: Vec2<i32>,
+        to: Vec2<i32>,
+    ) {
+        let (x0, x1) = (from.x.min(to.x), from.x.max(to.x));
+        let (z0, z1) = (from.y.min(to.y), from.y.max(to.y));
+        for x in x0..=x1 {
+            self.carve(x, from.y);
+        }
+        for z in z0..=z1 {
+            self.carve(to.x, z);
+        }
+    }
+
+    fn rooms(
+        rng: &mut Rng,
+        config: &Config,
+        min_room: usize,
+    ) -> Grid {
+        let mut grid = Grid::new(config.width, config.depth);
+        let min_room = min_room.max(2) as i32;
+        grid.split(
+            rng,
+            Vec2::new(1, 1),
+            Vec2::new(
+                config.width as i32 - 2,
+                config.depth as i32 - 2,
+            ),
+            min_room,
+        );
+        grid
+    }
+
+    /// Splits the leaf until it's too small to hold two
+    /// rooms, returns the center of one of the rooms
+    /// carved in it.
+    fn split(
+        &mut self,
+        rng: &mut Rng,
+        min: Vec2<i32>,
+        max: Vec2<i32>,
+        min_room: i32,
+    ) -> Vec2<i32> {
+        let size = max - min + 1;
+        // Rooms keep a tile of rock between them and the edge
+        // of their leaf.
+        let leaf = min_room + 2;
+        let can_split_x = size.x >= leaf * 2;
+        let can_split_z = size.y >= leaf * 2;
+
+        if !can_split_x && !can_split_z {
+            let room = Vec2::new(
+                rng.range(
+                    min_room as usize,
+                    size.x as usize - 1,
+                ),
+                rng.range(
+                    min_room as usize,
+                    size.y as usize - 1,
+                ),
+            )
+            .map(|e| e as i32);
+            let room = Vec2::partial_min(room, size - 2);
+            let from = min +
+                1 +
+                Vec2::new(
+                    rng.range(
+                        0,
+                        (size.x - room.x - 1) as usize,
+                    ),
+                    rng.range(
+                        0,
+                        (size.y - room.y - 1) as usize,
+                    ),
+                )
+                .map(|e| e as i32);
+            self.carve_rect(from, from + room - 1);
+            return from + room / 2;
+        }
+
+        let along_x = if can_split_x && can_split_z {
+            size.x > size.y ||
+                (size.x == size.y && rng.chance(0.5))
+        } else {
+            can_split_x
+        };
+
+        let (a, b) = if along_x {
+            let cut = min.x +
+                rng.range(
+                    leaf as usize,
+                    (size.x - leaf + 1) as usize,
+                ) as i32;
+            (
+                self.split(
+                    rng,
+                    min,
+                    Vec2::new(cut - 1, max.y),
+                    min_room,
+                ),
+                self.split(
+                    rng,
+                    Vec2::new(cut, min.y),
+                    max,
+                    min_room,
+                ),
+            )
+        } else {
+            let cut = min.y +
+                rng.range(
+                    leaf as usize,
+                    (size.y - leaf + 1) as usize,
+                ) as i32;
+            (
+                self.split(
+                    rng,
+                    min,
+                    Vec2::new(max.x, cut - 1),
+                    min_room,
+                ),
+                self.split(
+                    rng,
+                    Vec2::new(min.x, cut),
+                    max,
+                    min_room,
+                ),
+            )
+        };
+        self.carve_corridor(a, b);
+        if rng.chance(0.5) {
+            a
+        } else {
+            b
+        }
+    }
+
+    fn caves(
+        rng: &mut Rng,
+        config: &Config,
+        fill: f32,
+        steps: usize,
+    ) -> Grid {
+        let mut grid = Grid::new(config.width, config.depth);
+        for z in 0..config.depth as i32 {
+            for x in 0..config.width as i32 {
+                if !rng.chance(fill) {
+                    grid.carve(x, z);
+                }
+            }
+        }
+
+        for _ in 0..steps {
+            let mut next =
+                Grid::new(config.width, config.depth);
+            for z in 0..config.depth as i32 {
+                for x in 0..config.width as i32 {
+                    let mut rock = 0;
+                    for dz in -1..=1 {
+                        for dx in -1..=1 {
+                            if (dx, dz) != (0, 0) &&
+                                !grid.is_open(
+                                    x + dx,
+                                    z + dz,
+                                )
+                            {
+                                rock += 1;
+                            }
+                        }
+                    }
+                    if rock < 5 {
+                        next.carve(x, z);
+                    }
+                }
+            }
+            grid = next;
+        }
+        grid
+    }
+
+    /// Joins every open region to the previous one found with
+    /// a corridor, so the whole floor can be walked.
+    fn connect_regions(&mut self) {
+        let mut region = vec![None; self.open.len()];
+        let mut starts: Vec<Vec2<i32>> = Vec::new();
+
+        for z in 0..self.depth as i32 {
+            for x in 0..self.width as i32 {
+                let i = match self.index(x, z) {
+                    Some(i)
+                        if self.open[i] &&
+                            region[i].is_none() =>
+                    {
+                        i
+                    }
+                    _ => continue,
+                };
+                let id = starts.len();
+                starts.push(Vec2::new(x, z));
+                region[i] = Some(id);
+
+                let mut stack = vec![Vec2::new(x, z)];
+                while let Some(cell) = stack.pop() {
+                    for &(dx, dz) in
+                        &[(1, 0), (-1, 0), (0, 1), (0, -1)]
+                    {
+                        let next = cell + Vec2::new(dx, dz);
+                        if let Some(j) =
+                            self.index(next.x, next.y)
+                        {
+                            if self.open[j] &&
+                                region[j].is_none()
+                            {
+                                region[j] = Some(id);
+                                stack.push(next);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for pair in starts.windows(2) {
+            self.carve_corridor(pair[0], pair[1]);
+        }
+    }
+}
+
+/// Two flights of stairs with a landing in between, going
+/// from the rooms layer of one floor to the rooms layer of
+/// the next.
+struct Stairwell {
+    start: Vec2<i32>,
+    dir: Vec2<i32>,
+    facing: Facing,
+}
+
+impl Stairwell {
+    fn pick(rng: &mut Rng, config: &Config) -> Stairwell {
+        let (dir, facing) = if rng.chance(0.5) {
+            (Vec2::new(1, 0), Facing::East)
+        } else {
+            (Vec2::new(0, 1), Facing::South)
+        };
+        // Room for the way in, four tiles of stairwell and
+        // the way out, all off the outer ring.
+        let (long, wide) = if dir.x == 1 {
+            (config.width, config.depth)
+        } else {
+            (config.depth, config.width)
+        };
+        let along = rng.range(2, long - 5) as i32;
+        let across = rng.range(2, wide - 2) as i32;
+        let start = if dir.x == 1 {
+            Vec2::new(along, across)
+        } else {
+            Vec2::new(across, along)
+        };
+        Stairwell { start, dir, facing }
+    }
+
+    fn cell(&self, i: i32) -> Vec2<i32> {
+        self.start + self.dir * i
+    }
+
+    fn side(&self) -> Vec2<i32> {
+        Vec2::new(self.dir.y, self.dir.x)
+    }
+
+    /// The tiles around the stairwell from the `from`th to
+    /// the `to`th step along it.
+    fn around(
+        &self,
+        from: i32,
+        to: i32,
+    ) -> (Vec2<i32>, Vec2<i32>) {
+        let a = self.cell(from) - self.side();
+        let b = self.cell(to) + self.side();
+        (Vec2::partial_min(a, b), Vec2::partial_max(a, b))
+    }
+
+    /// The floor the stairs leave from, with a way around the
+    /// lower flight so it doesn't cut any corridor in two.
+    fn clear_departure(&self, grid: &mut Grid) {
+        let (min, max) = self.around(-1, 3);
+        grid.carve_rect(min, max);
+    }
+
+    /// Same for the floor the stairs arrive to and the
+    /// opening over the stairwell.
+    fn clear_arrival(&self, grid: &mut Grid) {
+        let (min, max) = self.around(-1, 4);
+        grid.carve_rect(min, max);
+    }
+
+    /// Whether this stairwell, leaving from the floor `below`
+    /// arrives to, gets in its way.
+    fn overlaps(&self, below: &Stairwell) -> bool {
+        let (a_min, a_max) = below.around(-2, 5);
+        let (b_min, b_max) = self.around(-2, 4);
+        a_min.x <= b_max.x &&
+            b_min.x <= a_max.x &&
+            a_min.y <= b_max.y &&
+            b_min.y <= a_max.y
+    }
+
+    /// Whether the stairwell takes the tile on any floor.
+    fn covers(&self, x: i32, z: i32) -> bool {
+        (0..4).any(|i| self.cell(i) == Vec2::new(x, z))
+    }
+
+    /// `rooms` is the rooms layer of the floor the stairs
+    /// leave from.
+    fn build(&self, area: &mut Area, rooms: i32) {
+        let at = |i: i32, y: i32| {
+            let cell = self.cell(i);
+            Vec3::new(cell.x, y, cell.y)
+        };
+        let stairs = Tile::Stairs(self.facing);
+
+        area.set_tile(at(0, rooms), stairs);
+        area.set_tile(at(1, rooms), Tile::Wall);
+        area.set_tile(at(2, rooms), Tile::Wall);
+
+        area.set_tile(at(0, rooms + 1), Tile::Air);
+        area.set_tile(at(1, rooms + 1), Tile::Air);
+        area.set_tile(at(2, rooms + 1), stairs);
+
+        for i in 0..3 {
+            area.set_tile(at(i, rooms + 2), Tile::Air);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caves() -> Algorithm {
+        Algorithm::Caves {
+            fill: 0.45,
+            steps: 4,
+        }
+    }
+
+    /// The rooms layer of the floor at `y` from above, walls
+    /// as `#`, the player start as `p`, enemies as `e` and
+    /// items as `i`.
+    fn plan(area: &Area, y: i32) -> Vec<String> {
+        let bounds = area.bounds();
+        (bounds.min.z..bounds.max.z)
+            .map(|z| {
+                (bounds.min.x..bounds.max.x)
+                    .map(|x| symbol(area, Vec3::new(x, y, z)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn symbol(area: &Area, point: Vec3<i32>) -> char {
+        let marker = area.markers().iter().find(|marker| {
+            marker.position.map(|e| e as i32) == point
+        });
+        match marker.map(|marker| &marker.kind) {
+            Some(MarkerKind::PlayerStart) => 'p',
+            Some(MarkerKind::Enemy(_)) => 'e',
+            Some(MarkerKind::Item(_)) => 'i',
+            _ if area.tile_at(point) == Some(Tile::Air) => {
+                '.'
+            }
+            _ => '#',
+        }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let configs = [
+            Config::default(),
+            Config {
+                floors: 3,
+                ..Config::default()
+            },
+            Config {
+                algorithm: caves(),
+                floors: 2,
+                ..Config::default()
+            },
+        ];
+        for config in &configs {
+            for seed in 0..5 {
+                assert_eq!(
+                    generate(seed, config),
+                    generate(seed, config)
+                );
+            }
+            assert_ne!(
+                generate(1, config),
+                generate(2, config)
+            );
+        }
+    }
+
+    #[test]
+    fn too_small() {
+        let config = Config {
+            width: MIN_SIZE - 1,
+            ..Config::default()
+        };
+        assert!(generate(1, &config).is_err());
+        let config = Config {
+            floors: 0,
+            ..Config::default()
+        };
+        assert!(generate(1, &config).is_err());
+    }
+
+    #[test]
+    fn rooms_snapshot() {
+        let config = Config {
+            width: 20,
+            depth: 14,
+            enemies: 2,
+            items: 1,
+            ..Config::default()
+        };
+        let area = generate(3, &config).unwrap();
+        assert_eq!(
+            plan(&area, 1),
+            [
+                "####################",
+                "####################",
+                "##....#####......###",
+                "##....#####......###",
+                "##...i.####......###",
+                "##.....####e.....###",
+                "######.########.####",
+                "######.########.####",
+                "####....#####....###",
+                "####....#####.e..###",
+                "####.............###",
+                "####....#####.p..###",
+                "####################",
+                "####################",
+            ]
+        );
+    }
+
+    #[test]
+    fn caves_snapshot() {
+        let config = Config {
+            width: 12,
+            depth: 12,
+            enemies: 1,
+            items: 1,
+            algorithm: caves(),
+            ..Config::default()
+        };
+        let area = generate(1, &config).unwrap();
+        assert_eq!(
+            plan(&area, 1),
+            [
+                "############",
+                "###.....####",
+                "##......####",
+                "#..p......##",
+                "#.....i.e###",
+                "#.........##",
+                "#.........##",
+                "#.........##",
+                "#.........##",
+                "###......###",
+                "############",
+                "############",
+            ]
+        );
+    }
+}
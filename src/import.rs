@@ -0,0 +1,2 @@
+pub mod tiled;
+pub mod vox;
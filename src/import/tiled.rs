@@ -0,0 +1,981 @@
+use std::{collections::HashMap, io::Read, path::Path};
+
+use vek::Vec3;
+
+use crate::map::{
+    facing_from_name, Area, MapError, Marker, MarkerKind,
+    Tile,
+};
+
+/// Tiled keeps the flip and rotation flags in the top bits of
+/// the tile ids.
+const FLAGS: u32 = 0xF000_0000;
+
+/// Which `Tile` each Tiled tile id becomes. Ids are global
+/// ids, counting from each tileset's `firstgid`, and 0 (no
+/// tile) is always air.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TileTable {
+    tiles: HashMap<u32, Tile>,
+}
+
+impl TileTable {
+    pub fn new() -> TileTable {
+        TileTable::default()
+    }
+
+    pub fn insert(&mut self, id: u32, tile: Tile) {
+        self.tiles.insert(id, tile);
+    }
+
+    pub fn get(&self, id: u32) -> Option<Tile> {
+        if id == 0 {
+            return Some(Tile::Air);
+        }
+        self.tiles.get(&id).copied()
+    }
+
+    /// A RON map from ids to tiles, `{ 1: Wall, 2:
+    /// Stairs(North) }`.
+    pub fn from_ron(
+        src: &str,
+    ) -> Result<TileTable, MapError> {
+        let tiles = ron::de::from_str(src)
+            .map_err(|e| MapError::Format(e.to_string()))?;
+        Ok(TileTable { tiles })
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<TileTable, MapError> {
+        let path = path.as_ref();
+        let src =
+            std::fs::read_to_string(path).map_err(|e| {
+                MapError::Io(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        TileTable::from_ron(&src)
+    }
+}
+
+/// Imports a map made with Tiled, `.tmx` files as XML and
+/// anything else as JSON.
+///
+/// Tile layers become floors from the ground up, the map's
+/// width and height being the area's width and depth. Object
+/// layers become markers on the floor of the tile layer right
+/// before them, or the one in their `floor` property. The
+/// object type picks the marker: `player`, `enemy`, `item`,
+/// `light` or `trigger`. Enemies and items take their kind
+/// from the `kind` property or the object name, lights their
+/// `radius` and `color` properties, triggers their name and
+/// size, with a `height` property in tiles. Anything can be
+/// turned with a `facing` property or by rotating the object.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    table: &TileTable,
+) -> Result<Area, MapError> {
+    let path = path.as_ref();
+    let src = std::fs::read_to_string(path).map_err(|e| {
+        MapError::Io(format!("{}: {}", path.display(), e))
+    })?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("tmx") => from_tmx(&src, table),
+        _ => from_tmj(&src, table),
+    }
+}
+
+/// Imports a map in Tiled's XML format.
+pub fn from_tmx(
+    src: &str,
+    table: &TileTable,
+) -> Result<Area, MapError> {
+    xml::parse(src)?.into_area(table)
+}
+
+/// Imports a map in Tiled's JSON format.
+pub fn from_tmj(
+    src: &str,
+    table: &TileTable,
+) -> Result<Area, MapError> {
+    json::parse(src)?.into_area(table)
+}
+
+/// What both formats get read into.
+struct TiledMap {
+    width: usize,
+    depth: usize,
+    tile_width: f32,
+    tile_height: f32,
+    layers: Vec<Layer>,
+}
+
+enum Layer {
+    Tiles {
+        name: String,
+        ids: Vec<u32>,
+    },
+    Objects {
+        floor: Option<i32>,
+        objects: Vec<Object>,
+    },
+}
+
+struct Object {
+    id: u32,
+    name: String,
+    kind: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    rotation: f32,
+    /// Tile objects are placed by their bottom left corner
+    /// instead of the top left.
+    is_tile: bool,
+    properties: HashMap<String, String>,
+}
+
+impl TiledMap {
+    fn into_area(
+        self,
+        table: &TileTable,
+    ) -> Result<Area, MapError> {
+        let floors = self
+            .layers
+            .iter()
+            .filter(|layer| match layer {
+                Layer::Tiles { .. } => true,
+                _ => false,
+            })
+            .count();
+        if floors == 0 {
+            return Err(MapError::Format(
+                "map has no tile layers".to_string(),
+            ));
+        }
+
+        let mut area =
+            Area::new(self.width, self.depth, floors);
+        let mut floor = -1;
+        for layer in &self.layers {
+            match layer {
+                Layer::Tiles { name, ids } => {
+                    floor += 1;
+                    if ids.len() != self.width * self.depth {
+                        return Err(MapError::Format(format!(
+                            "layer {:?} has {} tiles, expected {}",
+                            name,
+                            ids.len(),
+                            self.width * self.depth
+                        )));
+                    }
+                    for (i, id) in ids.iter().enumerate() {
+                        let (x, z) =
+                            (i % self.width, i / self.width);
+                        let tile = table.get(id & !FLAGS).ok_or_else(
+                            || {
+                                MapError::Format(format!(
+                                    "layer {:?} at {}, {}: no tile for id {}",
+                                    name,
+                                    x,
+                                    z,
+                                    id & !FLAGS
+                                ))
+                            },
+                        )?;
+                        area.set_tile(
+                            Vec3::new(
+                                x as i32, floor, z as i32,
+                            ),
+                            tile,
+                        );
+                    }
+                }
+                Layer::Objects {
+                    floor: layer_floor,
+                    objects,
+                } => {
+                    let floor = layer_floor
+                        .unwrap_or_else(|| floor.max(0));
+                    for object in objects {
+                        area.add_marker(
+                            self.marker(object, floor)?,
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(area)
+    }
+
+    fn marker(
+        &self,
+        object: &Object,
+        floor: i32,
+    ) -> Result<Marker, MapError> {
+        let error = |message: String| {
+            MapError::Format(format!(
+                "object {}: {}",
+                object.id, message
+            ))
+        };
+        let number =
+            |name: &str| -> Result<Option<f32>, MapError> {
+                match object.properties.get(name) {
+                    Some(value) => value
+                        .parse()
+                        .map(Some)
+                        .map_err(|_| {
+                            error(format!(
+                                "invalid {} {:?}",
+                                name, value
+                            ))
+                        }),
+                    None => Ok(None),
+                }
+            };
+        let kind = || {
+            object
+                .properties
+                .get("kind")
+                .cloned()
+                .or_else(|| Some(object.name.clone()))
+                .filter(|kind| !kind.is_empty())
+                .ok_or_else(|| {
+                    error("missing kind".to_string())
+                })
+        };
+
+        let top = if object.is_tile {
+            object.y - object.height
+        } else {
+            object.y
+        };
+        let position = Vec3::new(
+            (object.x + object.width / 2.0) / self.tile_width -
+                0.5,
+            floor as f32,
+            (top + object.height / 2.0) / self.tile_height -
+                0.5,
+        );
+
+        let marker_kind = match object.kind.as_str() {
+            "player" => MarkerKind::PlayerStart,
+            "enemy" => MarkerKind::Enemy(kind()?),
+            "item" => MarkerKind::Item(kind()?),
+            "light" => MarkerKind::Light {
+                radius: number("radius")?.unwrap_or(4.0),
+                color: match object.properties.get("color") {
+                    Some(color) => parse_color(color)
+                        .ok_or_else(|| {
+                            error(format!(
+                                "invalid color {:?}",
+                                color
+                            ))
+                        })?,
+                    None => (1.0, 1.0, 1.0),
+                },
+            },
+            "trigger" => MarkerKind::Trigger {
+                name: object.name.clone(),
+                size: (
+                    object.width / self.tile_width,
+                    number("height")?.unwrap_or(1.0),
+                    object.height / self.tile_height,
+                ),
+            },
+            other => {
+                return Err(error(format!(
+                    "unknown type {:?}",
+                    other
+                )))
+            }
+        };
+
+        let facing = match object.properties.get("facing") {
+            Some(facing) => facing_from_name(facing)
+                .or_else(|| facing.parse().ok())
+                .ok_or_else(|| {
+                    error(format!(
+                        "invalid facing {:?}",
+                        facing
+                    ))
+                })?,
+            None => object.rotation,
+        };
+
+        Ok(Marker::new(marker_kind, position).facing(facing))
+    }
+}
+
+/// Tiled colors are `#RRGGBB` or `#AARRGGBB`, alpha is
+/// ignored.
+fn parse_color(color: &str) -> Option<(f32, f32, f32)> {
+    let hex = color.trim_start_matches('#');
+    let rgb = match hex.len() {
+        6 => hex,
+        8 => hex.get(2..)?,
+        _ => return None,
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(rgb.get(i..i + 2)?, 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Decodes the tile ids of a layer, either comma separated or
+/// base64 with optional zlib or gzip compression.
+fn decode_ids(
+    data: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+) -> Result<Vec<u32>, MapError> {
+    match encoding {
+        Some("csv") => data
+            .split(',')
+            .map(|id| id.trim())
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse().map_err(|_| {
+                    MapError::Format(format!(
+                        "invalid tile id {:?}",
+                        id
+                    ))
+                })
+            })
+            .collect(),
+        Some("base64") => {
+            let bytes =
+                base64::decode(data.trim()).map_err(|e| {
+                    MapError::Format(e.to_string())
+                })?;
+            let bytes = match compression {
+                None | Some("") => bytes,
+                Some("zlib") => {
+                    inflate(flate2::read::ZlibDecoder::new(
+                        bytes.as_slice(),
+                    ))?
+                }
+                Some("gzip") => {
+                    inflate(flate2::read::GzDecoder::new(
+                        bytes.as_slice(),
+                    ))?
+                }
+                Some(other) => {
+                    return Err(MapError::Format(format!(
+                        "unsupported compression {:?}",
+                        other
+                    )))
+                }
+            };
+            if bytes.len() % 4 != 0 {
+                return Err(MapError::Format(
+                    "tile data isn't a whole number of ids"
+                        .to_string(),
+                ));
+            }
+            Ok(bytes
+                .chunks(4)
+                .map(|id| {
+                    u32::from_le_bytes([
+                        id[0], id[1], id[2], id[3],
+                    ])
+                })
+                .collect())
+        }
+        other => Err(MapError::Format(format!(
+            "unsupported encoding {:?}",
+            other
+        ))),
+    }
+}
+
+fn inflate<R: Read>(
+    mut decoder: R,
+) -> Result<Vec<u8>, MapError> {
+    let mut bytes = Vec::new();
+    decoder
+        .read_to_end(&mut bytes)
+        .map_err(|e| MapError::Format(e.to_string()))?;
+    Ok(bytes)
+}
+
+mod xml {
+    use std::collections::HashMap;
+
+    use roxmltree::{Document, Node};
+
+    use super::{decode_ids, Layer, Object, TiledMap};
+    use crate::map::MapError;
+
+    pub fn parse(src: &str) -> Result<TiledMap, MapError> {
+        let doc = Document::parse(src).map_err(|e| {
+            let pos = e.pos();
+            MapError::Parse {
+                line: pos.row as usize,
+                column: pos.col as usize,
+                message: e.to_string(),
+            }
+        })?;
+        let map = doc.root_element();
+        if !map.has_tag_name("map") {
+            return Err(error(
+                map,
+                "expected a <map>".to_string(),
+            ));
+        }
+        if map.attribute("infinite") == Some("1") {
+            return Err(error(
+                map,
+                "infinite maps aren't supported".to_string(),
+            ));
+        }
+
+        let mut layers = Vec::new();
+        read_layers(map, &mut layers)?;
+        Ok(TiledMap {
+            width: number(map, "width")?,
+            depth: number(map, "height")?,
+            tile_width: number(map, "tilewidth")?,
+            tile_height: number(map, "tileheight")?,
+            layers,
+        })
+    }
+
+    fn error(node: Node, message: String) -> MapError {
+        let pos =
+            node.document().text_pos_at(node.range().start);
+        MapError::Parse {
+            line: pos.row as usize,
+            column: pos.col as usize,
+            message,
+        }
+    }
+
+    fn number<T: std::str::FromStr>(
+        node: Node,
+        name: &str,
+    ) -> Result<T, MapError> {
+        let value =
+            node.attribute(name).ok_or_else(|| {
+                error(
+                    node,
+                    format!("missing attribute {:?}", name),
+                )
+            })?;
+        value.parse().map_err(|_| {
+            error(
+                node,
+                format!("invalid {} {:?}", name, value),
+            )
+        })
+    }
+
+    fn optional<T: std::str::FromStr + Default>(
+        node: Node,
+        name: &str,
+    ) -> Result<T, MapError> {
+        match node.attribute(name) {
+            Some(_) => number(node, name),
+            None => Ok(T::default()),
+        }
+    }
+
+    fn properties(node: Node) -> HashMap<String, String> {
+        node.children()
+            .filter(|n| n.has_tag_name("properties"))
+            .flat_map(|n| n.children())
+            .filter(|n| n.has_tag_name("property"))
+            .filter_map(|n| {
+                let value = n
+                    .attribute("value")
+                    .or_else(|| n.text())?;
+                Some((
+                    n.attribute("name")?.to_string(),
+                    value.to_string(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Groups are flattened into the layers around them.
+    fn read_layers(
+        parent: Node,
+        layers: &mut Vec<Layer>,
+    ) -> Result<(), MapError> {
+        for node in
+            parent.children().filter(|n| n.is_element())
+        {
+            match node.tag_name().name() {
+                "layer" => {
+                    let data = node
+                        .children()
+                        .find(|n| n.has_tag_name("data"))
+                        .ok_or_else(|| {
+                            error(
+                                node,
+                                "layer without data"
+                                    .to_string(),
+                            )
+                        })?;
+                    let ids = match data.attribute("encoding")
+                    {
+                        None => data
+                            .children()
+                            .filter(|n| {
+                                n.has_tag_name("tile")
+                            })
+                            .map(|n| optional(n, "gid"))
+                            .collect::<Result<_, _>>()?,
+                        encoding => decode_ids(
+                            data.text().unwrap_or(""),
+                            encoding,
+                            data.attribute("compression"),
+                        )
+                        .map_err(|e| {
+                            error(data, e.to_string())
+                        })?,
+                    };
+                    layers.push(Layer::Tiles {
+                        name: node
+                            .attribute("name")
+                            .unwrap_or("")
+                            .to_string(),
+                        ids,
+                    });
+                }
+                "objectgroup" => {
+                    let floor = match properties(node)
+                        .get("floor")
+                    {
+                        Some(floor) => Some(
+                            floor.parse().map_err(|_| {
+                                error(
+                                    node,
+                                    format!(
+                                        "invalid floor {:?}",
+                                        floor
+                                    ),
+                                )
+                            })?,
+                        ),
+                        None => None,
+                    };
+                    let objects = node
+                        .children()
+                        .filter(|n| n.has_tag_name("object"))
+                        .map(read_object)
+                        .collect::<Result<_, _>>()?;
+                    layers.push(Layer::Objects {
+                        floor,
+                        objects,
+                    });
+                }
+                "group" => read_layers(node, layers)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn read_object(node: Node) -> Result<Object, MapError> {
+        Ok(Object {
+            id: optional(node, "id")?,
+            name: node
+                .attribute("name")
+                .unwrap_or("")
+                .to_string(),
+            kind: node
+                .attribute("type")
+                .or_else(|| node.attribute("class"))
+                .unwrap_or("")
+                .to_string(),
+            x: number(node, "x")?,
+            y: number(node, "y")?,
+            width: optional(node, "width")?,
+            height: optional(node, "height")?,
+            rotation: optional(node, "rotation")?,
+            is_tile: node.attribute("gid").is_some(),
+            properties: properties(node),
+        })
+    }
+}
+
+mod json {
+    use std::{collections::HashMap, convert::TryFrom};
+
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    use super::{decode_ids, Layer, Object, TiledMap};
+    use crate::map::MapError;
+
+    #[derive(Deserialize)]
+    struct JsonMap {
+        width: usize,
+        height: usize,
+        tilewidth: f32,
+        tileheight: f32,
+        #[serde(default)]
+        infinite: bool,
+        layers: Vec<JsonLayer>,
+    }
+
+    #[derive(Deserialize)]
+    struct JsonLayer {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        name: String,
+        data: Option<Value>,
+        encoding: Option<String>,
+        compression: Option<String>,
+        #[serde(default)]
+        objects: Vec<JsonObject>,
+        #[serde(default)]
+        layers: Vec<JsonLayer>,
+        #[serde(default)]
+        properties: Vec<JsonProperty>,
+    }
+
+    #[derive(Deserialize)]
+    struct JsonObject {
+        #[serde(default)]
+        id: u32,
+        #[serde(default)]
+        name: String,
+        #[serde(default, rename = "type")]
+        kind: String,
+        #[serde(default)]
+        class: String,
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        height: f32,
+        #[serde(default)]
+        rotation: f32,
+        gid: Option<u32>,
+        #[serde(default)]
+        properties: Vec<JsonProperty>,
+    }
+
+    #[derive(Deserialize)]
+    struct JsonProperty {
+        name: String,
+        value: Value,
+    }
+
+    pub fn parse(src: &str) -> Result<TiledMap, MapError> {
+        let map: JsonMap = serde_json::from_str(src)
+            .map_err(|e| MapError::Parse {
+                line: e.line(),
+                column: e.column(),
+                message: e.to_string(),
+            })?;
+        if map.infinite {
+            return Err(MapError::Format(
+                "infinite maps aren't supported".to_string(),
+            ));
+        }
+
+        let mut layers = Vec::new();
+        read_layers(map.layers, &mut layers)?;
+        Ok(TiledMap {
+            width: map.width,
+            depth: map.height,
+            tile_width: map.tilewidth,
+            tile_height: map.tileheight,
+            layers,
+        })
+    }
+
+    fn properties(
+        properties: &[JsonProperty],
+    ) -> HashMap<String, String> {
+        properties
+            .iter()
+            .map(|p| {
+                let value = match &p.value {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                (p.name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn tile_id(
+        layer: &str,
+        id: &Value,
+    ) -> Result<u32, MapError> {
+        id.as_u64()
+            .and_then(|id| u32::try_from(id).ok())
+            .ok_or_else(|| {
+                MapError::Format(format!(
+                    "layer {:?}: invalid tile id {}",
+                    layer, id
+                ))
+            })
+    }
+
+    /// Groups are flattened into the layers around them.
+    fn read_layers(
+        json: Vec<JsonLayer>,
+        layers: &mut Vec<Layer>,
+    ) -> Result<(), MapError> {
+        for layer in json {
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    let ids = match &layer.data {
+                        Some(Value::Array(ids)) => ids
+                            .iter()
+                            .map(|id| {
+                                tile_id(&layer.name, id)
+                            })
+                            .collect::<Result<_, _>>()?,
+                        Some(Value::String(data)) => {
+                            decode_ids(
+                                data,
+                                layer.encoding.as_deref(),
+                                layer.compression.as_deref(),
+                            )?
+                        }
+                        _ => {
+                            return Err(MapError::Format(
+                                format!(
+                                    "layer {:?} without data",
+                                    layer.name
+                                ),
+                            ))
+                        }
+                    };
+                    layers.push(Layer::Tiles {
+                        name: layer.name,
+                        ids,
+                    });
+                }
+                "objectgroup" => {
+                    let floor = match properties(&layer.properties)
+                        .get("floor")
+                    {
+                        Some(floor) => Some(floor.parse().map_err(|_| {
+                            MapError::Format(format!(
+                                "layer {:?}: invalid floor {:?}",
+                                layer.name, floor
+                            ))
+                        })?),
+                        None => None,
+                    };
+                    let objects = layer
+                        .objects
+                        .into_iter()
+                        .map(|o| Object {
+                            id: o.id,
+                            name: o.name,
+                            kind: if o.kind.is_empty() {
+                                o.class
+                            } else {
+                                o.kind
+                            },
+                            x: o.x,
+                            y: o.y,
+                            width: o.width,
+                            height: o.height,
+                            rotation: o.rotation,
+                            is_tile: o.gid.is_some(),
+                            properties: properties(
+                                &o.properties,
+                            ),
+                        })
+                        .collect();
+                    layers.push(Layer::Objects {
+                        floor,
+                        objects,
+                    });
+                }
+                "group" => read_layers(layer.layers, layers)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX: &str = r#"<?xml version="1.0"?>
+<map width="2" height="2" tilewidth="16" tileheight="16">
+ <layer name="ground">
+  <data encoding="csv">2147483649,1,
+1,1</data>
+ </layer>
+ <layer name="zlib">
+  <data encoding="base64" compression="zlib">
+   eJxjYGBgYGJAAAAAKAAD
+  </data>
+ </layer>
+ <group>
+  <layer name="gzip">
+   <data encoding="base64" compression="gzip">
+    H4sIAAAAAAACA2NiQAUANm4baxAAAAA=
+   </data>
+  </layer>
+ </group>
+ <objectgroup name="things">
+  <properties>
+   <property name="floor" type="int" value="2"/>
+  </properties>
+  <object id="1" type="enemy" name="rat" x="16" y="16"/>
+  <object id="2" gid="2" type="item" name="key"
+          x="0" y="32" width="16" height="16"/>
+ </objectgroup>
+</map>"#;
+
+    const TMJ: &str = r#"{
+ "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16,
+ "layers": [
+  {"type": "tilelayer", "name": "ground",
+   "data": [2147483649, 1, 1, 1]},
+  {"type": "tilelayer", "name": "zlib",
+   "encoding": "base64", "compression": "zlib",
+   "data": "eJxjYGBgYGJAAAAAKAAD"},
+  {"type": "group", "layers": [
+   {"type": "tilelayer", "name": "gzip",
+    "encoding": "base64", "compression": "gzip",
+    "data": "H4sIAAAAAAACA2NiQAUANm4baxAAAAA="}
+  ]},
+  {"type": "objectgroup", "name": "things",
+   "properties": [{"name": "floor", "value": 2}],
+   "objects": [
+    {"id": 1, "type": "enemy", "name": "rat",
+     "x": 16, "y": 16},
+    {"id": 2, "gid": 2, "type": "item", "name": "key",
+     "x": 0, "y": 32, "width": 16, "height": 16}
+   ]}
+ ]
+}"#;
+
+    fn table() -> TileTable {
+        let mut table = TileTable::new();
+        table.insert(1, Tile::Wall);
+        table.insert(2, Tile::Crate);
+        table
+    }
+
+    fn check(area: &Area) {
+        assert_eq!(area.height(), 3);
+        // The first id has the horizontal flip flag set.
+        for z in 0..2 {
+            for x in 0..2 {
+                let point = Vec3::new(x, 0, z);
+                assert_eq!(
+                    area.tile_at(point),
+                    Some(Tile::Wall)
+                );
+            }
+        }
+        let crates: Vec<Vec3<i32>> = area
+            .filled()
+            .filter(|(_, tile)| *tile == Tile::Crate)
+            .map(|(point, _)| point)
+            .collect();
+        assert_eq!(crates.len(), 2);
+        assert!(crates.contains(&Vec3::new(1, 1, 0)));
+        assert!(crates.contains(&Vec3::new(0, 2, 0)));
+
+        let markers = area.markers();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(
+            markers[0].kind,
+            MarkerKind::Enemy("rat".to_string())
+        );
+        assert_eq!(
+            markers[0].position,
+            Vec3::new(0.5, 2.0, 0.5)
+        );
+        // Tile objects hang up from their bottom corner.
+        assert_eq!(
+            markers[1].kind,
+            MarkerKind::Item("key".to_string())
+        );
+        assert_eq!(
+            markers[1].position,
+            Vec3::new(0.0, 2.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn imports_tmx() {
+        check(&from_tmx(TMX, &table()).unwrap());
+    }
+
+    #[test]
+    fn imports_tmj() {
+        check(&from_tmj(TMJ, &table()).unwrap());
+    }
+
+    fn error(result: Result<Area, MapError>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unknown_tile_id() {
+        let tmx = TMX.replace("1,1</data>", "1,3</data>");
+        assert!(error(from_tmx(&tmx, &table()))
+            .contains("no tile for id 3"));
+        let tmj = TMJ.replace("1, 1, 1]", "1, 1, 3]");
+        assert!(error(from_tmj(&tmj, &table()))
+            .contains("no tile for id 3"));
+        let tmj =
+            TMJ.replace("1, 1, 1]", "1, 1, 4294967297]");
+        assert!(error(from_tmj(&tmj, &table()))
+            .contains("invalid tile id"));
+    }
+
+    #[test]
+    fn layer_of_wrong_length() {
+        let tmx = TMX.replace("1,1</data>", "1</data>");
+        assert!(error(from_tmx(&tmx, &table()))
+            .contains("has 3 tiles, expected 4"));
+        let tmj = TMJ.replace("1, 1, 1]", "1, 1]");
+        assert!(error(from_tmj(&tmj, &table()))
+            .contains("has 3 tiles, expected 4"));
+    }
+
+    #[test]
+    fn infinite_maps() {
+        let tmx =
+            TMX.replace("<map ", r#"<map infinite="1" "#);
+        assert!(error(from_tmx(&tmx, &table()))
+            .contains("infinite maps aren't supported"));
+        let tmj =
+            TMJ.replace("{\n", "{\"infinite\": true,\n");
+        assert!(error(from_tmj(&tmj, &table()))
+            .contains("infinite maps aren't supported"));
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(
+            parse_color("#ff0000"),
+            Some((1.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            parse_color("#80ffffff"),
+            Some((1.0, 1.0, 1.0))
+        );
+        assert_eq!(parse_color("#ééé"), None);
+        assert_eq!(parse_color("#ffééé"), None);
+    }
+}
@@ -0,0 +1,476 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use vek::Vec3;
+
+use crate::map::{Area, Face, MapError, Tile};
+
+/// Largest a model can be on each axis, MagicaVoxel doesn't
+/// make them any bigger.
+const MAX_SIZE: u32 = 256;
+
+/// The tile kind a palette color becomes and the textures it
+/// gives that kind.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PaletteEntry {
+    pub tile: Tile,
+    #[serde(default)]
+    pub top: Option<String>,
+    #[serde(default)]
+    pub bottom: Option<String>,
+    #[serde(default)]
+    pub side: Option<String>,
+}
+
+impl PaletteEntry {
+    fn textures(&self) -> impl Iterator<Item = (Face, &str)> {
+        vec![
+            (Face::Top, self.top.as_deref()),
+            (Face::Bottom, self.bottom.as_deref()),
+            (Face::Side, self.side.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(face, texture)| Some((face, texture?)))
+    }
+}
+
+/// Which tile each palette index (1 to 255) becomes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaletteTable {
+    entries: HashMap<u8, PaletteEntry>,
+}
+
+impl PaletteTable {
+    pub fn new() -> PaletteTable {
+        PaletteTable::default()
+    }
+
+    pub fn insert(&mut self, index: u8, entry: PaletteEntry) {
+        self.entries.insert(index, entry);
+    }
+
+    /// A RON map from palette indices to entries, `{ 1:
+    /// (tile: Wall, side:
+    /// Some("assets/stone_wall_b.png")) }`.
+    pub fn from_ron(
+        src: &str,
+    ) -> Result<PaletteTable, MapError> {
+        let entries = ron::de::from_str(src)
+            .map_err(|e| MapError::Format(e.to_string()))?;
+        Ok(PaletteTable { entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<PaletteTable, MapError> {
+        let path = path.as_ref();
+        let src =
+            std::fs::read_to_string(path).map_err(|e| {
+                MapError::Io(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        PaletteTable::from_ron(&src)
+    }
+
+    fn get(
+        &self,
+        index: u8,
+    ) -> Result<&PaletteEntry, MapError> {
+        self.entries.get(&index).ok_or_else(|| {
+            MapError::Format(format!(
+                "no tile for palette index {}",
+                index
+            ))
+        })
+    }
+}
+
+/// One model of a `.vox` file, already in world axes: x
+/// stays, MagicaVoxel's z is our y and its y runs towards
+/// north.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    pub size: Vec3<i32>,
+    /// Position and palette index of every voxel.
+    pub voxels: Vec<(Vec3<i32>, u8)>,
+}
+
+impl Model {
+    /// An area holding just the model.
+    pub fn to_area(
+        &self,
+        table: &PaletteTable,
+    ) -> Result<Area, MapError> {
+        let mut area = Area::new(
+            self.size.x as usize,
+            self.size.z as usize,
+            self.size.y as usize,
+        );
+        self.stamp(&mut area, Vec3::zero(), table)?;
+        Ok(area)
+    }
+
+    /// Places the model with its corner at `offset`,
+    /// replacing the tiles it covers and leaving the rest
+    /// alone. Nothing changes if a palette index has no
+    /// tile or a texture clashes with the ones already in
+    /// the area.
+    pub fn stamp(
+        &self,
+        area: &mut Area,
+        offset: Vec3<i32>,
+        table: &PaletteTable,
+    ) -> Result<(), MapError> {
+        let mut used: Vec<u8> = self
+            .voxels
+            .iter()
+            .map(|(_, index)| *index)
+            .collect();
+        used.sort();
+        used.dedup();
+
+        let mut textures = Vec::new();
+        for index in used {
+            let entry = table.get(index)?;
+            for (face, texture) in entry.textures() {
+                let current = area
+                    .materials()
+                    .get(entry.tile, face)
+                    .or_else(|| {
+                        textures
+                            .iter()
+                            .find(|(t, f, _)| {
+                                *t == entry.tile && *f == face
+                            })
+                            .map(|(_, _, texture)| *texture)
+                    });
+                match current {
+                    Some(current) if current != texture => {
+                        return Err(MapError::Format(format!(
+                            "palette index {} clashes with the \
+                             {} {} texture {:?}",
+                            index,
+                            entry.tile.name(),
+                            face.name(),
+                            current
+                        )))
+                    }
+                    _ => textures.push((entry.tile, face, texture)),
+                }
+            }
+        }
+
+        for (tile, face, texture) in textures {
+            area.materials_mut().set(
+                tile,
+                face,
+                texture.to_string(),
+            );
+        }
+        for (point, index) in &self.voxels {
+            area.set_tile(
+                offset + *point,
+                table.get(*index)?.tile,
+            );
+        }
+        Ok(())
+    }
+}
+
+pub fn load<P: AsRef<Path>>(
+    path: P,
+) -> Result<Vec<Model>, MapError> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| {
+        MapError::Io(format!("{}: {}", path.display(), e))
+    })?;
+    from_bytes(&bytes)
+}
+
+/// Reads every model in a `.vox` file, in the order they're
+/// stored. The scene graph isn't read, so models keep their
+/// own origin.
+pub fn from_bytes(
+    bytes: &[u8],
+) -> Result<Vec<Model>, MapError> {
+    let mut reader = Reader { bytes, at: 0 };
+    if reader.take(4)? != b"VOX " {
+        return Err(MapError::Format(
+            "not a vox file".to_string(),
+        ));
+    }
+    reader.u32()?;
+
+    let (id, content, children) = reader.chunk()?;
+    if id != b"MAIN" {
+        return Err(MapError::Format(
+            "expected a MAIN chunk".to_string(),
+        ));
+    }
+    reader.take(content)?;
+
+    let end = reader.at.saturating_add(children);
+    let mut models = Vec::new();
+    let mut size = None;
+    while reader.at < end {
+        let (id, content, children) = reader.chunk()?;
+        let mut chunk = Reader {
+            bytes: reader.take(content)?,
+            at: 0,
+        };
+        reader.take(children)?;
+
+        match id {
+            b"SIZE" => {
+                let read = Vec3::new(
+                    chunk.u32()?,
+                    chunk.u32()?,
+                    chunk.u32()?,
+                );
+                if read
+                    .iter()
+                    .any(|e| *e == 0 || *e > MAX_SIZE)
+                {
+                    return Err(MapError::Format(format!(
+                        "invalid model size {}x{}x{}",
+                        read.x, read.y, read.z
+                    )));
+                }
+                size = Some(read);
+            }
+            b"XYZI" => {
+                let size = size.take().ok_or_else(|| {
+                    MapError::Format(
+                        "XYZI chunk without a SIZE"
+                            .to_string(),
+                    )
+                })?;
+                let mut voxels = Vec::new();
+                for _ in 0..chunk.u32()? {
+                    let voxel = chunk.take(4)?;
+                    let (x, y, z) = (
+                        voxel[0] as u32,
+                        voxel[1] as u32,
+                        voxel[2] as u32,
+                    );
+                    if x >= size.x ||
+                        y >= size.y ||
+                        z >= size.z
+                    {
+                        return Err(MapError::Format(format!(
+                            "voxel {}, {}, {} outside of the model",
+                            x, y, z
+                        )));
+                    }
+                    voxels.push((
+                        Vec3::new(x, z, size.y - 1 - y)
+                            .map(|e| e as i32),
+                        voxel[3],
+                    ));
+                }
+                models.push(Model {
+                    size: Vec3::new(size.x, size.z, size.y)
+                        .map(|e| e as i32),
+                    voxels,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(models)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(
+        &mut self,
+        n: usize,
+    ) -> Result<&'a [u8], MapError> {
+        let bytes = self
+            .at
+            .checked_add(n)
+            .and_then(|end| self.bytes.get(self.at..end))
+            .ok_or_else(|| {
+                MapError::Format(format!(
+                    "unexpected end of file at byte {}",
+                    self.at
+                ))
+            })?;
+        self.at += n;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, MapError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    /// A chunk header, its id and the size of its content and
+    /// children.
+    fn chunk(
+        &mut self,
+    ) -> Result<(&'a [u8], usize, usize), MapError> {
+        let id = self.take(4)?;
+        Ok((id, self.u32()? as usize, self.u32()? as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend(&(content.len() as u32).to_le_bytes());
+        bytes.extend(&0u32.to_le_bytes());
+        bytes.extend(content);
+        bytes
+    }
+
+    fn size(x: u32, y: u32, z: u32) -> Vec<u8> {
+        let mut content = Vec::new();
+        for e in &[x, y, z] {
+            content.extend(&e.to_le_bytes());
+        }
+        chunk(b"SIZE", &content)
+    }
+
+    fn xyzi(voxels: &[[u8; 4]]) -> Vec<u8> {
+        let mut content =
+            (voxels.len() as u32).to_le_bytes().to_vec();
+        for voxel in voxels {
+            content.extend(voxel);
+        }
+        chunk(b"XYZI", &content)
+    }
+
+    fn vox(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let children = chunks.concat();
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(&150u32.to_le_bytes());
+        bytes.extend(b"MAIN");
+        bytes.extend(&0u32.to_le_bytes());
+        bytes.extend(&(children.len() as u32).to_le_bytes());
+        bytes.extend(children);
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        from_bytes(bytes).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn reads_models() {
+        let bytes = vox(&[
+            size(2, 3, 4),
+            xyzi(&[[0, 0, 0, 1], [1, 0, 2, 5]]),
+            chunk(b"RGBA", &[0; 8]),
+            size(1, 1, 1),
+            xyzi(&[]),
+        ]);
+        let models = from_bytes(&bytes).unwrap();
+        assert_eq!(
+            models,
+            vec![
+                Model {
+                    size: Vec3::new(2, 4, 3),
+                    voxels: vec![
+                        (Vec3::new(0, 0, 2), 1),
+                        (Vec3::new(1, 2, 2), 5),
+                    ],
+                },
+                Model {
+                    size: Vec3::one(),
+                    voxels: Vec::new(),
+                },
+            ]
+        );
+
+        for len in 0..bytes.len() {
+            assert!(
+                from_bytes(&bytes[..len]).is_err(),
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn bad_models() {
+        assert!(error(&vox(&[xyzi(&[[0, 0, 0, 1]])]))
+            .contains("without a SIZE"));
+        assert!(error(&vox(&[
+            size(2, 2, 2),
+            xyzi(&[[0, 2, 0, 1]]),
+        ]))
+        .contains("outside of the model"));
+        for bad in &[(0, 1, 1), (1, 257, 1), (1, 1, u32::MAX)]
+        {
+            let bytes = vox(&[size(bad.0, bad.1, bad.2)]);
+            assert!(
+                error(&bytes).contains("invalid model size")
+            );
+        }
+        assert!(
+            from_bytes(&vox(&[size(256, 256, 256)])).is_ok()
+        );
+    }
+
+    #[test]
+    fn stamp_leaves_area_alone_on_clash() {
+        let mut table = PaletteTable::new();
+        table.insert(
+            1,
+            PaletteEntry {
+                tile: Tile::Wall,
+                top: None,
+                bottom: None,
+                side: Some("b.png".to_string()),
+            },
+        );
+        let model = Model {
+            size: Vec3::one(),
+            voxels: vec![(Vec3::zero(), 1)],
+        };
+
+        let mut area = Area::new(2, 2, 2);
+        area.materials_mut().set(
+            Tile::Wall,
+            Face::Side,
+            "a.png".to_string(),
+        );
+        assert!(model
+            .stamp(&mut area, Vec3::one(), &table)
+            .unwrap_err()
+            .to_string()
+            .contains("clashes"));
+        assert_eq!(
+            area.tile_at(Vec3::one()),
+            Some(Tile::Air)
+        );
+        assert_eq!(
+            area.materials().get(Tile::Wall, Face::Side),
+            Some("a.png")
+        );
+
+        let mut area = Area::new(2, 2, 2);
+        model.stamp(&mut area, Vec3::one(), &table).unwrap();
+        assert_eq!(
+            area.tile_at(Vec3::one()),
+            Some(Tile::Wall)
+        );
+        assert_eq!(
+            area.materials().get(Tile::Wall, Face::Side),
+            Some("b.png")
+        );
+    }
+}
@@ -1,35 +1,76 @@
-use sdl2::keyboard::Keycode;
+use sdl2::{keyboard::Keycode, mouse::MouseButton};
 use std::collections::HashSet;
 
 pub struct Input {
     pressed_keys: HashSet<Keycode>,
+    /// Keys that went down this frame.
+    just_pressed: HashSet<Keycode>,
+    /// Buttons clicked this frame.
+    clicked: HashSet<MouseButton>,
     mouse_rel: (i32, i32),
+    wheel: i32,
 }
 
 impl Input {
     pub fn new() -> Input {
         Input {
             pressed_keys: HashSet::new(),
+            just_pressed: HashSet::new(),
+            clicked: HashSet::new(),
             mouse_rel: (0, 0),
+            wheel: 0,
         }
     }
 
-    pub fn set_mouse(&mut self, xrel: i32, yrel: i32) {
-        self.mouse_rel = (xrel, yrel);
+    /// Forgets everything that only lasts a frame, called
+    /// after every simulation step so each one is seen once.
+    /// Frames that don't step keep it for the next one.
+    pub fn clear_frame(&mut self) {
+        self.just_pressed.clear();
+        self.clicked.clear();
+        self.mouse_rel = (0, 0);
+        self.wheel = 0;
+    }
+
+    /// Adds to how far the mouse moved since the last
+    /// `clear_frame`.
+    pub fn move_mouse(&mut self, xrel: i32, yrel: i32) {
+        self.mouse_rel.0 += xrel;
+        self.mouse_rel.1 += yrel;
     }
 
     pub fn press(&mut self, key: Keycode) {
-        self.pressed_keys.insert(key);
+        if self.pressed_keys.insert(key) {
+            self.just_pressed.insert(key);
+        }
     }
 
     pub fn release(&mut self, key: Keycode) {
         self.pressed_keys.remove(&key);
     }
 
+    pub fn click(&mut self, button: MouseButton) {
+        self.clicked.insert(button);
+    }
+
+    pub fn scroll(&mut self, amount: i32) {
+        self.wheel += amount;
+    }
+
     pub fn is_pressed(&self, key: Keycode) -> bool {
         self.pressed_keys.contains(&key)
     }
 
+    /// Whether the key went down this frame, ignoring key
+    /// repeats.
+    pub fn was_pressed(&self, key: Keycode) -> bool {
+        self.just_pressed.contains(&key)
+    }
+
+    pub fn was_clicked(&self, button: MouseButton) -> bool {
+        self.clicked.contains(&button)
+    }
+
     pub fn mouse_x(&self) -> f32 {
         self.mouse_rel.0 as f32
     }
@@ -37,4 +78,10 @@ impl Input {
     pub fn mouse_y(&self) -> f32 {
         self.mouse_rel.1 as f32
     }
+
+    /// How far the wheel turned this frame, positive away
+    /// from the user.
+    pub fn wheel(&self) -> i32 {
+        self.wheel
+    }
 }
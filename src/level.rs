@@ -0,0 +1,132 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Deserialize;
+use vek::Vec3;
+
+use crate::map::{Area, MapError};
+
+/// Where an exit tile of a level takes the player.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Exit {
+    /// The exit tile.
+    pub at: (i32, i32, i32),
+    /// Name of the level it leads to.
+    pub to: String,
+    /// Where the player shows up, the player start of the
+    /// level when missing.
+    #[serde(default)]
+    pub spawn: Option<(f32, f32, f32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Level {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub exits: Vec<Exit>,
+}
+
+/// Every level of the game by name, and the one being played.
+///
+/// ```text
+/// (
+///     start: "debug",
+///     levels: {
+///         "debug": (
+///             path: "assets/maps/debug.map",
+///             exits: [(at: (16, 1, 5), to: "cellar")],
+///         ),
+///         "cellar": (path: "assets/maps/cellar.map"),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Levels {
+    start: String,
+    levels: HashMap<String, Level>,
+    #[serde(skip)]
+    current: String,
+}
+
+impl Levels {
+    pub fn from_ron(src: &str) -> Result<Levels, MapError> {
+        let mut levels: Levels = ron::de::from_str(src)
+            .map_err(|e| MapError::Format(e.to_string()))?;
+
+        let missing = |name: &String| {
+            MapError::Format(format!(
+                "no level named {:?}",
+                name
+            ))
+        };
+        if !levels.levels.contains_key(&levels.start) {
+            return Err(missing(&levels.start));
+        }
+        for level in levels.levels.values() {
+            for exit in &level.exits {
+                if !levels.levels.contains_key(&exit.to) {
+                    return Err(missing(&exit.to));
+                }
+            }
+        }
+
+        levels.current = levels.start.clone();
+        Ok(levels)
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(
+        path: P,
+    ) -> Result<Levels, MapError> {
+        let path = path.as_ref();
+        let src =
+            std::fs::read_to_string(path).map_err(|e| {
+                MapError::Io(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Levels::from_ron(&src)
+    }
+
+    /// Name of the level being played.
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    pub fn current_level(&self) -> &Level {
+        &self.levels[&self.current]
+    }
+
+    pub fn level(&self, name: &str) -> Option<&Level> {
+        self.levels.get(name)
+    }
+
+    /// Makes `name` the level being played, the area has to
+    /// be swapped separately.
+    pub fn enter(&mut self, name: &str) {
+        if self.levels.contains_key(name) {
+            self.current = name.to_string();
+        }
+    }
+
+    pub fn load_area(
+        &self,
+        name: &str,
+    ) -> Result<Area, MapError> {
+        let level = self.level(name).ok_or_else(|| {
+            MapError::Format(format!(
+                "no level named {:?}",
+                name
+            ))
+        })?;
+        Area::load(&level.path)
+    }
+
+    /// The exit of the current level on the tile at `point`.
+    pub fn exit_at(&self, point: Vec3<i32>) -> Option<&Exit> {
+        self.current_level()
+            .exits
+            .iter()
+            .find(|exit| Vec3::from(exit.at) == point)
+    }
+}
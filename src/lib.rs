@@ -0,0 +1,16 @@
+#![feature(clamp)]
+
+//! Everything that works without a window: the maps, the
+//! tools built on them and the components the game moves
+//! around. Shared by the game and `mapcheck`.
+
+pub mod analysis;
+pub mod component;
+pub mod generate;
+pub mod import;
+pub mod level;
+pub mod lighting;
+pub mod map;
+pub mod pathfind;
+pub mod util;
+pub mod visibility;
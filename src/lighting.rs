@@ -0,0 +1,360 @@
+use std::collections::{HashMap, VecDeque};
+
+use vek::{Aabb, Vec3};
+
+use crate::map::Area;
+
+/// Brightest a tile can be, light loses a level for every
+/// tile it travels.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Brightness of a tile no light reaches.
+const AMBIENT: f32 = 0.08;
+
+/// How much dimmer each level below `MAX_LIGHT` looks.
+const FALLOFF: f32 = 0.82;
+
+const SIDES: [(i32, i32, i32); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Light levels of the tiles of an `Area`, from 0 to
+/// `MAX_LIGHT`.
+///
+/// Light spreads from emissive tiles like torches and lava
+/// through every tile that isn't opaque. With skylight, the
+/// open tiles of the top floor are fully lit and that light
+/// goes straight down without getting dimmer, spreading to
+/// the sides like any other.
+pub struct LightMap {
+    sky: bool,
+    /// Goes up every time the light changes.
+    revision: u64,
+    bounds: Aabb<i32>,
+    block: HashMap<Vec3<i32>, u8>,
+    skylight: HashMap<Vec3<i32>, u8>,
+}
+
+impl LightMap {
+    pub fn new(area: &Area, sky: bool) -> LightMap {
+        let mut lights = LightMap {
+            sky,
+            revision: 0,
+            bounds: area.bounds(),
+            block: HashMap::new(),
+            skylight: HashMap::new(),
+        };
+        lights.rebuild(area);
+        lights
+    }
+
+    /// The light level of the tile at `point`.
+    pub fn get(&self, point: Vec3<i32>) -> u8 {
+        self.block(point).max(self.sky(point))
+    }
+
+    /// Light at `point` coming from emissive tiles.
+    pub fn block(&self, point: Vec3<i32>) -> u8 {
+        self.block.get(&point).copied().unwrap_or(0)
+    }
+
+    /// Light at `point` coming from the sky.
+    pub fn sky(&self, point: Vec3<i32>) -> u8 {
+        self.skylight.get(&point).copied().unwrap_or(0)
+    }
+
+    /// How bright the tile at `point` looks, from 0 to 1.
+    pub fn brightness(&self, point: Vec3<i32>) -> f32 {
+        brightness(self.get(point))
+    }
+
+    /// How bright a vertex at `corner` of a face looking
+    /// towards `normal` is, from 0 to 1. It's the average of
+    /// the tiles touching the corner in front of the face
+    /// that light goes through, so the light blends from one
+    /// tile to the next instead of changing face by face.
+    pub fn corner_brightness(
+        &self,
+        area: &Area,
+        corner: Vec3<f32>,
+        normal: Vec3<i32>,
+    ) -> f32 {
+        let front = corner + normal.map(|e| e as f32) * 0.01;
+        let cells = Aabb {
+            min: front.map(|e| (e - 0.001).round() as i32),
+            max: front.map(|e| (e + 0.001).round() as i32),
+        };
+
+        let mut total = 0.0;
+        let mut count = 0;
+        for y in cells.min.y..=cells.max.y {
+            for z in cells.min.z..=cells.max.z {
+                for x in cells.min.x..=cells.max.x {
+                    let cell = Vec3::new(x, y, z);
+                    if transparent(area, cell) {
+                        total += self.brightness(cell);
+                        count += 1;
+                    }
+                }
+            }
+        }
+        if count == 0 {
+            brightness(0)
+        } else {
+            total / count as f32
+        }
+    }
+
+    /// Changes every time any light level may have, to find
+    /// out when whatever was lit with it is out of date.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Recomputes every light level.
+    pub fn rebuild(&mut self, area: &Area) {
+        self.revision += 1;
+        self.bounds = area.bounds();
+        self.block.clear();
+        self.skylight.clear();
+
+        let mut open = VecDeque::new();
+        for point in area.points() {
+            let level = source(area, false, point);
+            if level > 0 {
+                self.block.insert(point, level);
+                open.push_back(point);
+            }
+        }
+        spread(area, false, &mut self.block, open);
+
+        if self.sky {
+            let mut open = VecDeque::new();
+            for point in area.points() {
+                let level = source(area, true, point);
+                if level > 0 {
+                    self.skylight.insert(point, level);
+                    open.push_back(point);
+                }
+            }
+            spread(area, true, &mut self.skylight, open);
+        }
+    }
+
+    /// Fixes the light around `point` after the tile there
+    /// changed, only going over the tiles that it affects.
+    pub fn update(&mut self, area: &Area, point: Vec3<i32>) {
+        if area.bounds() != self.bounds {
+            self.rebuild(area);
+            return;
+        }
+        self.revision += 1;
+
+        relight(area, false, &mut self.block, point);
+        if self.sky {
+            relight(area, true, &mut self.skylight, point);
+        }
+    }
+}
+
+fn brightness(level: u8) -> f32 {
+    AMBIENT.max(FALLOFF.powi((MAX_LIGHT - level) as i32))
+}
+
+fn transparent(area: &Area, point: Vec3<i32>) -> bool {
+    area.tile_at(point).map_or(false, |t| !t.is_opaque())
+}
+
+/// The light the tile at `point` starts with before any
+/// spreads to it.
+fn source(area: &Area, sky: bool, point: Vec3<i32>) -> u8 {
+    match area.tile_at(point) {
+        Some(tile) if sky => {
+            if !tile.is_opaque() &&
+                point.y == area.bounds().max.y - 1
+            {
+                MAX_LIGHT
+            } else {
+                0
+            }
+        }
+        Some(tile) => tile.emission(),
+        None => 0,
+    }
+}
+
+/// The level light at `level` has after going in the
+/// direction of `side`.
+fn after_step(sky: bool, level: u8, side: Vec3<i32>) -> u8 {
+    if sky && level == MAX_LIGHT && side == -Vec3::unit_y() {
+        level
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Breadth first from the tiles in `open`, raising the ones
+/// light reaches brighter than they were.
+fn spread(
+    area: &Area,
+    sky: bool,
+    levels: &mut HashMap<Vec3<i32>, u8>,
+    mut open: VecDeque<Vec3<i32>>,
+) {
+    while let Some(point) = open.pop_front() {
+        let level = levels.get(&point).copied().unwrap_or(0);
+        for &(x, y, z) in &SIDES {
+            let side = Vec3::new(x, y, z);
+            let next = point + side;
+            let next_level = after_step(sky, level, side);
+            if next_level > 0 &&
+                transparent(area, next) &&
+                levels
+                    .get(&next)
+                    .map_or(true, |l| *l < next_level)
+            {
+                levels.insert(next, next_level);
+                open.push_back(next);
+            }
+        }
+    }
+}
+
+/// Takes out the light that came through `point` and spreads
+/// it again from whatever still lights the tiles around.
+fn relight(
+    area: &Area,
+    sky: bool,
+    levels: &mut HashMap<Vec3<i32>, u8>,
+    point: Vec3<i32>,
+) {
+    let mut dark = VecDeque::new();
+    let mut open = VecDeque::new();
+    let mut darkened = vec![point];
+    if let Some(level) = levels.remove(&point) {
+        dark.push_back((point, level));
+    }
+
+    while let Some((point, level)) = dark.pop_front() {
+        for &(x, y, z) in &SIDES {
+            let side = Vec3::new(x, y, z);
+            let next = point + side;
+            let next_level = match levels.get(&next) {
+                Some(l) => *l,
+                None => continue,
+            };
+            // Anything as dim as the light it got from here
+            // could have come from here, anything brighter is
+            // lit some other way and fills the gap back in.
+            if next_level <= after_step(sky, level, side) {
+                levels.remove(&next);
+                dark.push_back((next, next_level));
+                darkened.push(next);
+            } else {
+                open.push_back(next);
+            }
+        }
+    }
+
+    for &(x, y, z) in &SIDES {
+        let side = point + Vec3::new(x, y, z);
+        if levels.contains_key(&side) {
+            open.push_back(side);
+        }
+    }
+
+    // Sources that got darkened light themselves again.
+    for point in darkened {
+        let level = source(area, sky, point);
+        if level > 0 {
+            levels.insert(point, level);
+            open.push_back(point);
+        }
+    }
+
+    spread(area, sky, levels, open);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate::Rng, map::Tile};
+
+    fn assert_same(area: &Area, lights: &LightMap) {
+        let fresh = LightMap::new(area, lights.sky);
+        for point in area.points() {
+            assert_eq!(
+                (lights.block(point), lights.sky(point)),
+                (fresh.block(point), fresh.sky(point)),
+                "at {:?}",
+                point
+            );
+        }
+    }
+
+    #[test]
+    fn update_matches_rebuild() {
+        let tiles = [
+            Tile::Air,
+            Tile::Air,
+            Tile::Wall,
+            Tile::Torch,
+            Tile::Lava,
+            Tile::Grate,
+        ];
+        for &sky in &[false, true] {
+            let mut area =
+                Area::load("assets/maps/debug.map").unwrap();
+            let mut lights = LightMap::new(&area, sky);
+            let mut rng = Rng::new(7);
+            for _ in 0..200 {
+                let point = area.bounds().min +
+                    Vec3::new(
+                        rng.range(0, area.width()),
+                        rng.range(0, area.height()),
+                        rng.range(0, area.depth()),
+                    )
+                    .map(|e| e as i32);
+                let tile = tiles[rng.range(0, tiles.len())];
+                area.set_tile(point, tile);
+                lights.update(&area, point);
+                assert_same(&area, &lights);
+            }
+        }
+    }
+    #[test]
+    fn corners_blend_the_light_in_front() {
+        let mut area = Area::new(7, 3, 3);
+        for x in 0..7 {
+            for z in 0..3 {
+                area.set_tile(Vec3::new(x, 0, z), Tile::Wall);
+            }
+        }
+        area.set_tile(Vec3::new(1, 1, 1), Tile::Torch);
+        let lights = LightMap::new(&area, false);
+        let up = Vec3::unit_y();
+
+        let near = Vec3::new(1.5, 0.5, 1.5);
+        let far = Vec3::new(5.5, 0.5, 1.5);
+        assert!(
+            lights.corner_brightness(&area, near, up) >
+                lights.corner_brightness(&area, far, up)
+        );
+
+        // Walls in front of the corner don't darken it.
+        area.set_tile(Vec3::new(2, 1, 1), Tile::Wall);
+        area.set_tile(Vec3::new(2, 1, 2), Tile::Wall);
+        let lights = LightMap::new(&area, false);
+        let open = (lights.brightness(Vec3::new(1, 1, 1)) +
+            lights.brightness(Vec3::new(1, 1, 2))) /
+            2.0;
+        let corner =
+            lights.corner_brightness(&area, near, up);
+        assert!((corner - open).abs() < 1e-6, "{}", corner);
+    }
+}
@@ -1,35 +1,46 @@
-#![feature(clamp)]
-
 use bevy::app::App;
 use gl;
+use openglsdl::{
+    component, level::Levels, lighting, map, util,
+};
 use sdl2::{self, event::Event};
+use std::{collections::HashMap, time::Instant};
 use vek::{Mat4, Vec3};
 
-mod component;
 mod draw;
 mod init;
 mod input;
-mod map;
 mod mesh;
 mod plugin;
 mod shader;
 mod texture;
-mod util;
 
 use draw::Draw;
 use input::Input;
-use mesh::Mesh;
+use mesh::{Mesh, Terrain};
 use shader::ShaderProgram;
 use texture::Texture;
 
-use component::{Position, Rotation};
-use plugin::{BasePlugin, GamePlugin};
+use component::{Player, Position, Rotation};
+use plugin::{
+    BasePlugin, Editor, EditorPlugin, GamePlugin, TIMESTEP,
+};
 
 type Result<T> = std::result::Result<T, String>;
 
 pub const WINDOW_WIDTH: f32 = 800.0;
 pub const WINDOW_HEIGHT: f32 = 600.0;
 
+const LEVELS_PATH: &str = "assets/levels.ron";
+
+/// Whether open ceilings let light in.
+const SKYLIGHT: bool = true;
+
+/// Most simulation steps run for a single frame, when frames
+/// take longer than this the simulation slows down instead of
+/// falling further behind.
+const MAX_STEPS: u32 = 5;
+
 fn main() -> Result<()> {
     let sdl_context = sdl2::init()?;
     let video = sdl_context.video()?;
@@ -52,13 +63,25 @@ fn main() -> Result<()> {
     sdl_context.mouse().set_relative_mouse_mode(true);
 
     let colvec: Vec<Vec3<i32>> = Vec::new();
+    let levels = Levels::load(LEVELS_PATH)
+        .map_err(|e| e.to_string())?;
+    let area = levels
+        .load_area(levels.current())
+        .map_err(|e| e.to_string())?;
+    let lights = lighting::LightMap::new(&area, SKYLIGHT);
+    let mut terrain = Terrain::new(&area, &lights);
     let mut bevy = std::mem::replace(
         &mut App::build()
             .add_plugin(BasePlugin)
             .add_plugin(GamePlugin)
+            .add_plugin(EditorPlugin {
+                path: levels.current_level().path.clone(),
+            })
             .add_resource(Input::new())
             .add_resource(colvec)
-            .add_resource(map::Area::debug())
+            .add_resource(area)
+            .add_resource(lights)
+            .add_resource(levels)
             .app,
         App::default(),
     );
@@ -108,7 +131,6 @@ fn main() -> Result<()> {
         ])
         .finalize();
     let cube = Mesh::build().cube().finalize();
-
     let wall_texture =
         Texture::new("assets/stone_wall_b.png");
 
@@ -147,7 +169,14 @@ fn main() -> Result<()> {
     );
     */
 
-    let area = map::Area::debug();
+    let mut level = String::new();
+    let mut textures: HashMap<String, Texture> =
+        HashMap::new();
+
+    // Time the simulation still has to catch up on.
+    let mut accumulator = 0.0;
+    let mut last_frame = Instant::now();
+
     'running: loop {
         unsafe {
             gl::ClearColor(0.005, 0.0, 0.15, 1.0);
@@ -160,7 +189,6 @@ fn main() -> Result<()> {
         {
             let mut input =
                 bevy.resources.get_mut::<Input>().unwrap();
-            input.set_mouse(0, 0);
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. } |
@@ -184,20 +212,69 @@ fn main() -> Result<()> {
                     Event::MouseMotion {
                         xrel, yrel, ..
                     } => {
-                        input.set_mouse(xrel, yrel);
+                        input.move_mouse(xrel, yrel);
+                    }
+                    Event::MouseButtonDown {
+                        mouse_btn,
+                        ..
+                    } => {
+                        input.click(mouse_btn);
+                    }
+                    Event::MouseWheel { y, .. } => {
+                        input.scroll(y);
                     }
                     _ => {}
                 }
             }
         }
 
-        bevy.update();
+        let now = Instant::now();
+        accumulator += (now - last_frame).as_secs_f32();
+        accumulator =
+            accumulator.min(TIMESTEP * MAX_STEPS as f32);
+        last_frame = now;
+        while accumulator >= TIMESTEP {
+            bevy.update();
+            bevy.resources
+                .get_mut::<Input>()
+                .unwrap()
+                .clear_frame();
+            accumulator -= TIMESTEP;
+        }
+        // How far along the next step the frame is drawn.
+        let alpha = accumulator / TIMESTEP;
+
+        // Swap the textures when the level changes, the old
+        // ones get freed as they're dropped.
+        {
+            let levels =
+                bevy.resources.get::<Levels>().unwrap();
+            if levels.current() != level {
+                level = levels.current().to_string();
+                textures = bevy
+                    .resources
+                    .get::<map::Area>()
+                    .unwrap()
+                    .materials()
+                    .textures()
+                    .into_iter()
+                    .map(|path| {
+                        (path.to_string(), Texture::new(path))
+                    })
+                    .collect();
+                bevy.resources
+                    .get_mut::<Editor>()
+                    .unwrap()
+                    .path =
+                    levels.current_level().path.clone();
+            }
+        }
 
         let mut view = Mat4::identity();
 
-        for (pos, dir) in bevy
+        for (_, pos, dir) in bevy
             .world
-            .query::<(&Position, &Rotation)>()
+            .query::<(&Player, &Position, &Rotation)>()
             .iter()
         {
             /*
@@ -207,8 +284,9 @@ fn main() -> Result<()> {
             )
             .inverse();
             */
-            view = Mat4::from(dir.quat.conjugate())
-                .translated_3d(pos.internal())
+            let rotation = dir.interpolated(alpha);
+            view = Mat4::from(rotation.conjugate())
+                .translated_3d(pos.interpolated(alpha))
                 .inverted();
         }
 
@@ -229,57 +307,75 @@ fn main() -> Result<()> {
 
         let colvec =
             bevy.resources.get::<Vec<Vec3<i32>>>().unwrap();
-        for z in 0..4 {
-            for x in 0..20 {
-                for y in 0..8 {
-                    let t = &area.tiles
-                        [(20 * y + x) + z * 20 * 8];
-                    if t.is_wall() {
-                        let mvp = projection *
-                            view *
-                            Mat4::translation_3d(
-                                Vec3::new(
-                                    x as f32, z as f32,
-                                    y as f32,
-                                ),
-                            );
-                        Draw::with(&program)
-                            .with_matrix("mvp", &mvp)
-                            .with_texture_n(&wall_texture, 0)
-                            .mesh(&cube);
-                    } else {
-                        /*
-                        // Ceiling and floor?
-                        let d = Draw::with(&program)
-                            .with_texture_n(
-                                &floor_texture,
-                                0,
-                            );
-
-                        let mvp = projection *
-                            view *
-                            Mat4::from_translation(
-                                Vec3::new(
-                                    x as f32, 1.0, y as f32,
-                                ),
-                            );
-                        let d = d
-                            .with_matrix("mvp", &mvp)
-                            .mesh(&cube);
-
-                        let mvp = projection *
-                            view *
-                            Mat4::from_translation(
-                                Vec3::new(
-                                    x as f32, -1.0, y as f32,
-                                ),
-                            );
-                        d.with_matrix("mvp", &mvp)
-                            .mesh(&cube);
-                            */
-                    }
+        let area = bevy.resources.get::<map::Area>().unwrap();
+        let lights = bevy
+            .resources
+            .get::<lighting::LightMap>()
+            .unwrap();
+        if terrain.is_stale(&lights) {
+            terrain = Terrain::new(&area, &lights);
+        }
+        let mvp = projection * view;
+        for (face, material, mesh) in &terrain.meshes {
+            let texture = material
+                .as_ref()
+                .and_then(|path| textures.get(path))
+                .unwrap_or(match face {
+                    map::Face::Side => &wall_texture,
+                    _ => &floor_texture,
+                });
+            Draw::with(&program)
+                .with_matrix("mvp", &mvp)
+                .with_texture_n(texture, 0)
+                .mesh(mesh);
+        }
+
+        let editor = bevy.resources.get::<Editor>().unwrap();
+        if editor.enabled {
+            if let Some(hit) = editor.target() {
+                let mvp = projection *
+                    view *
+                    Mat4::translation_3d(
+                        hit.tile.map(|e| e as f32),
+                    ) *
+                    Mat4::scaling_3d(Vec3::broadcast(1.02));
+                unsafe {
+                    gl::PolygonMode(
+                        gl::FRONT_AND_BACK,
+                        gl::LINE,
+                    );
+                }
+                Draw::with(&program)
+                    .with_matrix("mvp", &mvp)
+                    .with_texture_n(&floor_texture, 0)
+                    .mesh(&cube);
+                unsafe {
+                    gl::PolygonMode(
+                        gl::FRONT_AND_BACK,
+                        gl::FILL,
+                    );
                 }
             }
+
+            // Crosshair
+            let aspect = WINDOW_WIDTH / WINDOW_HEIGHT;
+            unsafe {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            Draw::with(&program)
+                .with_matrix(
+                    "mvp",
+                    &Mat4::scaling_3d(Vec3::new(
+                        0.01,
+                        0.01 * aspect,
+                        1.0,
+                    )),
+                )
+                .with_texture_n(&wall_texture, 0)
+                .mesh(&plane);
+            unsafe {
+                gl::Enable(gl::DEPTH_TEST);
+            }
         }
 
         window.gl_swap_window();
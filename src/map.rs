@@ -1,158 +1,415 @@
+use std::{collections::HashMap, path::Path};
+
 use vek::{Aabb, Vec3};
 
-use crate::component::BoundingBox;
-
-const STR_MAP: [&'static str; 4] = [
-    r#"
-    #################### 
-    ####################
-    ####################
-    ####################
-    ####################
-    ####################
-    ####################
-    ####################
-"#,
-    r#"
-    ###############.#### 
-    #.......######..####
-    #..............#####
-    #..............#####
-    #.......#####.######
-    #.......####..##.###
-    #.......###.......##
-    ####################
-"#,
-    r#"
-    #################### 
-    #.................##
-    #.................##
-    #.................##
-    #..................#
-    #######............#
-    ####################
-    ####################
-    "#,
-    r#"
-    #################### 
-    ####...#############
-    ####################
-    ####################
-    ####################
-    ####################
-    ####################
-    ####################
-    "#,
-];
-
-#[derive(PartialEq)]
-pub enum Tile {
-    Floor,
-    Wall,
-}
+use chunk::Chunks;
 
-impl Tile {
-    fn from_char(ch: char) -> Tile {
-        match ch {
-            '#' => Tile::Wall,
-            _ => Tile::Floor,
-        }
-    }
+mod chunk;
+mod damage;
+mod marker;
+mod material;
+mod parse;
+mod raycast;
+mod serial;
+mod sweep;
+mod tile;
+mod write;
 
-    pub fn is_wall(&self) -> bool {
-        *self == Tile::Wall
-    }
+pub use damage::Damage;
+pub use marker::{facing_from_name, Marker, MarkerKind};
+pub use material::{Face, Materials};
+pub use parse::{MapError, SourceMap};
+pub use raycast::RayHit;
+pub use sweep::Sweep;
+pub use tile::{Facing, Tile};
+
+/// A tile replaced through `Area::set_tile`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileChanged {
+    pub point: Vec3<i32>,
+    pub previous: Tile,
+    pub tile: Tile,
 }
 
+/// The tiles of a level in world axes, x is the width, z the
+/// depth and y the floors. Tiles are stored in sparse chunks,
+/// so coordinates can go negative and areas can be as large
+/// as they need to be.
+#[derive(Debug)]
 pub struct Area {
-    pub tiles: Vec<Tile>,
+    /// Everything outside of it is out of bounds, `max` is
+    /// exclusive. Grows as tiles are set outside of it.
+    bounds: Aabb<i32>,
+    chunks: Chunks,
+    materials: Materials,
+    markers: Vec<Marker>,
+    /// Damage taken by the breakable tiles still standing.
+    damage: HashMap<Vec3<i32>, u16>,
+    /// Changes waiting to be picked up, `None` until someone
+    /// asks to track them.
+    changes: Option<Vec<TileChanged>>,
 }
 
 impl Area {
-    pub fn debug() -> Area {
-        let mut tiles = Vec::new();
-        for z in &STR_MAP {
-            tiles.append(
-                &mut z
-                    .to_owned()
-                    .replace('\n', "")
-                    .replace(' ', "")
-                    .chars()
-                    .map(|ch| Tile::from_char(ch))
-                    .collect(),
-            );
+    pub fn new(
+        width: usize,
+        depth: usize,
+        height: usize,
+    ) -> Area {
+        Area::with_bounds(Aabb {
+            min: Vec3::zero(),
+            max: Vec3::new(
+                width as i32,
+                height as i32,
+                depth as i32,
+            ),
+        })
+    }
+
+    /// An empty area, `bounds.max` is exclusive.
+    pub fn with_bounds(bounds: Aabb<i32>) -> Area {
+        Area {
+            bounds,
+            chunks: Chunks::new(),
+            materials: Materials::new(),
+            markers: Vec::new(),
+            damage: HashMap::new(),
+            changes: None,
         }
-        Area { tiles }
     }
 
-    pub fn blocks_at(
-        &self,
-        point: Vec3<i32>,
-    ) -> Option<Vec3<i32>> {
-        if point.x < 0 || point.y < 0 || point.z < 0 {
+    /// Loads an area, picking the format from the extension:
+    /// `.ron`, binary `.area`, or the text format described
+    /// in `parse` for anything else.
+    pub fn load<P>(path: P) -> Result<Area, MapError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let io_error = |e: std::io::Error| {
+            MapError::Io(format!("{}: {}", path.display(), e))
+        };
+        match extension(path) {
+            Some("area") => {
+                let bytes =
+                    std::fs::read(path).map_err(io_error)?;
+                Area::from_bytes(&bytes)
+            }
+            Some("ron") => {
+                let src = std::fs::read_to_string(path)
+                    .map_err(io_error)?;
+                Area::from_ron(&src)
+            }
+            _ => {
+                let src = std::fs::read_to_string(path)
+                    .map_err(io_error)?;
+                Area::parse(&src)
+            }
+        }
+    }
+
+    /// Saves the area as `.ron`, binary `.area` or the `.map`
+    /// text format depending on the extension.
+    pub fn save<P>(&self, path: P) -> Result<(), MapError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let bytes = match extension(path) {
+            Some("area") => self.to_bytes(),
+            Some("ron") => self.to_ron().into_bytes(),
+            Some("map") => self.to_text().into_bytes(),
+            _ => {
+                return Err(MapError::Io(format!(
+                "{}: can only save .map, .area or .ron files",
+                path.display()
+            )))
+            }
+        };
+        std::fs::write(path, bytes).map_err(|e| {
+            MapError::Io(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn parse(src: &str) -> Result<Area, MapError> {
+        parse::parse(src).map(|(area, _)| area)
+    }
+
+    /// Like `parse`, also telling where everything came from
+    /// in `src`.
+    pub fn parse_with_source(
+        src: &str,
+    ) -> Result<(Area, SourceMap), MapError> {
+        parse::parse(src)
+    }
+
+    pub fn to_text(&self) -> String {
+        write::write(self)
+    }
+
+    pub fn to_ron(&self) -> String {
+        serial::to_ron(self)
+    }
+
+    pub fn from_ron(src: &str) -> Result<Area, MapError> {
+        serial::from_ron(src)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serial::to_bytes(self)
+    }
+
+    pub fn from_bytes(
+        bytes: &[u8],
+    ) -> Result<Area, MapError> {
+        serial::from_bytes(bytes)
+    }
+
+    pub fn bounds(&self) -> Aabb<i32> {
+        self.bounds
+    }
+
+    pub fn width(&self) -> usize {
+        (self.bounds.max.x - self.bounds.min.x) as usize
+    }
+
+    pub fn depth(&self) -> usize {
+        (self.bounds.max.z - self.bounds.min.z) as usize
+    }
+
+    pub fn height(&self) -> usize {
+        (self.bounds.max.y - self.bounds.min.y) as usize
+    }
+
+    pub fn materials(&self) -> &Materials {
+        &self.materials
+    }
+
+    pub fn materials_mut(&mut self) -> &mut Materials {
+        &mut self.materials
+    }
+
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    pub fn add_marker(&mut self, marker: Marker) {
+        self.markers.push(marker);
+    }
+
+    /// The first player start marker.
+    pub fn player_start(&self) -> Option<&Marker> {
+        self.markers
+            .iter()
+            .find(|m| m.kind == MarkerKind::PlayerStart)
+    }
+
+    pub fn contains(&self, point: Vec3<i32>) -> bool {
+        point.x >= self.bounds.min.x &&
+            point.y >= self.bounds.min.y &&
+            point.z >= self.bounds.min.z &&
+            point.x < self.bounds.max.x &&
+            point.y < self.bounds.max.y &&
+            point.z < self.bounds.max.z
+    }
+
+    /// `None` if `point` is out of bounds.
+    pub fn tile_at(&self, point: Vec3<i32>) -> Option<Tile> {
+        if !self.contains(point) {
             return None;
         }
-        if let Some(tile) = self.tiles.get(
-            (20 * point.z as usize + point.x as usize) +
-                point.y as usize * 20 * 8,
-        ) {
-            if tile.is_wall() {
-                return Some(point);
+        Some(self.chunks.get(point))
+    }
+
+    /// Returns the replaced tile. Setting anything other than
+    /// air out of bounds grows the bounds to fit it, see
+    /// `track_changes` to find out what changed.
+    pub fn set_tile(
+        &mut self,
+        point: Vec3<i32>,
+        tile: Tile,
+    ) -> Tile {
+        if !self.contains(point) {
+            if tile == Tile::Air {
+                return Tile::Air;
+            }
+            self.bounds.min =
+                Vec3::partial_min(self.bounds.min, point);
+            self.bounds.max =
+                Vec3::partial_max(self.bounds.max, point + 1);
+        }
+        let previous = self.chunks.set(point, tile);
+        if previous != tile {
+            self.damage.remove(&point);
+        }
+        if let Some(changes) = &mut self.changes {
+            if previous != tile {
+                changes.push(TileChanged {
+                    point,
+                    previous,
+                    tile,
+                });
             }
         }
-        None
+        previous
     }
 
-    pub fn blocks_around(
+    /// From now on keeps every tile that `set_tile` changes
+    /// until they're taken.
+    pub fn track_changes(&mut self) {
+        if self.changes.is_none() {
+            self.changes = Some(Vec::new());
+        }
+    }
+
+    /// The changes since the last call, oldest first.
+    pub fn take_changes(&mut self) -> Vec<TileChanged> {
+        match &mut self.changes {
+            Some(changes) => std::mem::take(changes),
+            None => Vec::new(),
+        }
+    }
+
+    /// Every position inside the bounds, floor by floor and
+    /// row by row.
+    pub fn points(&self) -> impl Iterator<Item = Vec3<i32>> {
+        let Aabb { min, max } = self.bounds;
+        (min.y..max.y).flat_map(move |y| {
+            (min.z..max.z).flat_map(move |z| {
+                (min.x..max.x)
+                    .map(move |x| Vec3::new(x, y, z))
+            })
+        })
+    }
+
+    /// Every tile in the area along with its position, in the
+    /// same order as `points`.
+    pub fn iter(
         &self,
-        point: Vec3<f32>,
-    ) -> Option<(Aabb<f32>, Aabb<f32>, Vec3<i32>)> {
-        let aabb = Aabb {
-            min: Vec3::new(
-                point.x - 0.25,
-                point.y - 0.35,
-                point.z - 0.25,
-            ),
-            max: Vec3::new(
-                point.x + 0.25,
-                point.y + 0.15,
-                point.z + 0.25,
-            ),
+    ) -> impl Iterator<Item = (Vec3<i32>, Tile)> + '_ {
+        self.points().map(move |p| (p, self.chunks.get(p)))
+    }
+
+    /// Only the tiles that aren't air, in no particular
+    /// order. Cheaper than `iter` on large, mostly empty
+    /// areas.
+    pub fn filled(
+        &self,
+    ) -> impl Iterator<Item = (Vec3<i32>, Tile)> + '_ {
+        self.chunks.filled()
+    }
+
+    /// The first tile `direction` runs into starting from
+    /// `origin`, up to `max_distance` away.
+    pub fn raycast(
+        &self,
+        origin: Vec3<f32>,
+        direction: Vec3<f32>,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        raycast::raycast(
+            self,
+            origin,
+            direction,
+            max_distance,
+        )
+    }
+
+    /// How much more damage the tile at `point` can take,
+    /// `None` if it can't be broken.
+    pub fn hit_points(
+        &self,
+        point: Vec3<i32>,
+    ) -> Option<u16> {
+        let durability = self.tile_at(point)?.durability()?;
+        let taken = self.damage.get(&point).copied();
+        Some(durability.saturating_sub(taken.unwrap_or(0)))
+    }
+
+    /// Takes `amount` off the hit points of the tile at
+    /// `point`, replacing it with its debris once they run
+    /// out. Returns whether it broke.
+    pub fn damage(
+        &mut self,
+        point: Vec3<i32>,
+        amount: u16,
+    ) -> bool {
+        let tile = match self.tile_at(point) {
+            Some(tile) => tile,
+            None => return false,
         };
+        let durability = match tile.durability() {
+            Some(durability) => durability,
+            None => return false,
+        };
+        let taken = self.damage.entry(point).or_insert(0);
+        *taken = taken.saturating_add(amount);
+        if *taken < durability {
+            return false;
+        }
+        self.set_tile(point, tile.debris());
+        true
+    }
 
-        let area_rect = aabb.map(|e| e.round() as i32);
-
-        for my in area_rect.min.y..=area_rect.max.y {
-            for mx in area_rect.min.x..=area_rect.max.x {
-                for mz in area_rect.min.z..=area_rect.max.z {
-                    let thisblocks =
-                        self.blocks_at((mx, my, mz).into());
-                    if let Some(block) = thisblocks {
-                        let block_aabb = Aabb {
-                            min: Vec3::new(
-                                mx as f32 - 0.5,
-                                my as f32 - 0.5,
-                                mz as f32 - 0.5,
-                            ),
-                            max: Vec3::new(
-                                mx as f32 + 0.5,
-                                my as f32 + 0.5,
-                                mz as f32 + 0.5,
-                            ),
-                        };
-                        if aabb.collides_with_aabb(block_aabb)
-                        {
-                            return Some((
-                                block_aabb,
-                                aabb,
-                                Vec3::new(mx, my, mz),
-                            ));
-                        }
-                    }
-                }
-            }
+    /// Hurts every tile `damage` reaches, returns the ones
+    /// that broke.
+    pub fn apply_damage(
+        &mut self,
+        damage: &Damage,
+    ) -> Vec<Vec3<i32>> {
+        damage::targets(self, damage)
+            .into_iter()
+            .filter(|(point, amount)| {
+                self.damage(*point, *amount)
+            })
+            .map(|(point, _)| point)
+            .collect()
+    }
+
+    /// Moves `aabb` by `motion` until it runs into the
+    /// collision of the tiles, see `sweep::sweep`.
+    pub fn sweep(
+        &self,
+        aabb: Aabb<f32>,
+        motion: Vec3<f32>,
+    ) -> Sweep {
+        sweep::sweep(self, aabb, motion)
+    }
+
+    /// Like `sweep`, climbing up and down ledges up to
+    /// `step_height` tall, see `sweep::walk`.
+    pub fn walk(
+        &self,
+        aabb: Aabb<f32>,
+        motion: Vec3<f32>,
+        step_height: f32,
+    ) -> Sweep {
+        sweep::walk(self, aabb, motion, step_height)
+    }
+
+    pub fn blocks_at(
+        &self,
+        point: Vec3<i32>,
+    ) -> Option<Vec3<i32>> {
+        match self.tile_at(point) {
+            Some(tile) if tile.is_solid() => Some(point),
+            _ => None,
         }
+    }
+}
 
-        None
+/// Areas are equal when they'd save the same, the damage
+/// taken by tiles and the changes being tracked only last
+/// while the level is played.
+impl PartialEq for Area {
+    fn eq(&self, other: &Area) -> bool {
+        self.bounds == other.bounds &&
+            self.chunks == other.chunks &&
+            self.materials == other.materials &&
+            self.markers == other.markers
     }
 }
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|ext| ext.to_str())
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use vek::Vec3;
+
+use super::Tile;
+
+pub const CHUNK_SIZE: i32 = 16;
+
+const CHUNK_VOLUME: usize =
+    (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Chunk {
+    tiles: Vec<Tile>,
+    /// How many tiles aren't air, the chunk gets dropped
+    /// once it reaches zero.
+    filled: usize,
+}
+
+impl Chunk {
+    fn new() -> Chunk {
+        Chunk {
+            tiles: vec![Tile::Air; CHUNK_VOLUME],
+            filled: 0,
+        }
+    }
+}
+
+/// Tiles split in `CHUNK_SIZE`³ chunks, only chunks with
+/// something other than air in them are stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chunks {
+    chunks: HashMap<Vec3<i32>, Chunk>,
+}
+
+/// The chunk a tile belongs to and its index inside of it.
+fn locate(point: Vec3<i32>) -> (Vec3<i32>, usize) {
+    let chunk = point.map(|e| e.div_euclid(CHUNK_SIZE));
+    let local = point.map(|e| e.rem_euclid(CHUNK_SIZE));
+    let index = local.x +
+        local.z * CHUNK_SIZE +
+        local.y * CHUNK_SIZE * CHUNK_SIZE;
+    (chunk, index as usize)
+}
+
+impl Chunks {
+    pub fn new() -> Chunks {
+        Chunks::default()
+    }
+
+    pub fn get(&self, point: Vec3<i32>) -> Tile {
+        let (chunk, index) = locate(point);
+        match self.chunks.get(&chunk) {
+            Some(chunk) => chunk.tiles[index],
+            None => Tile::Air,
+        }
+    }
+
+    /// Returns the tile that was replaced.
+    pub fn set(
+        &mut self,
+        point: Vec3<i32>,
+        tile: Tile,
+    ) -> Tile {
+        let (key, index) = locate(point);
+        if tile == Tile::Air &&
+            !self.chunks.contains_key(&key)
+        {
+            return Tile::Air;
+        }
+
+        let chunk =
+            self.chunks.entry(key).or_insert_with(Chunk::new);
+        let previous =
+            std::mem::replace(&mut chunk.tiles[index], tile);
+        match (previous == Tile::Air, tile == Tile::Air) {
+            (true, false) => chunk.filled += 1,
+            (false, true) => chunk.filled -= 1,
+            _ => {}
+        }
+        if chunk.filled == 0 {
+            self.chunks.remove(&key);
+        }
+
+        previous
+    }
+
+    /// Every tile that isn't air, in no particular order.
+    pub fn filled(
+        &self,
+    ) -> impl Iterator<Item = (Vec3<i32>, Tile)> + '_ {
+        self.chunks.iter().flat_map(|(key, chunk)| {
+            let origin = *key * CHUNK_SIZE;
+            chunk.tiles.iter().enumerate().filter_map(
+                move |(i, tile)| {
+                    if *tile == Tile::Air {
+                        return None;
+                    }
+                    let i = i as i32;
+                    let local = Vec3::new(
+                        i % CHUNK_SIZE,
+                        i / (CHUNK_SIZE * CHUNK_SIZE),
+                        i / CHUNK_SIZE % CHUNK_SIZE,
+                    );
+                    Some((origin + local, *tile))
+                },
+            )
+        })
+    }
+}
@@ -0,0 +1,168 @@
+use vek::Vec3;
+
+use super::Area;
+
+/// Something hurting the tiles of an area, see
+/// `Area::apply_damage`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Damage {
+    /// Hurts the first tile the ray runs into.
+    Ray {
+        origin: Vec3<f32>,
+        direction: Vec3<f32>,
+        reach: f32,
+        amount: u16,
+    },
+    /// Hurts every tile whose center is within `radius`, the
+    /// closer to `center` the harder.
+    Explosion {
+        center: Vec3<f32>,
+        radius: f32,
+        amount: u16,
+    },
+}
+
+/// The tiles `damage` reaches with how much it hurts each.
+pub fn targets(
+    area: &Area,
+    damage: &Damage,
+) -> Vec<(Vec3<i32>, u16)> {
+    match *damage {
+        Damage::Ray {
+            origin,
+            direction,
+            reach,
+            amount,
+        } => area
+            .raycast(origin, direction, reach)
+            .map(|hit| (hit.tile, amount))
+            .into_iter()
+            .collect(),
+        Damage::Explosion {
+            center,
+            radius,
+            amount,
+        } => {
+            let min =
+                (center - radius).map(|e| e.round() as i32);
+            let max =
+                (center + radius).map(|e| e.round() as i32);
+            let mut targets = Vec::new();
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    for x in min.x..=max.x {
+                        let point = Vec3::new(x, y, z);
+                        let distance = point
+                            .map(|e| e as f32)
+                            .distance(center);
+                        if distance > radius {
+                            continue;
+                        }
+                        let falloff = 1.0 - distance / radius;
+                        let hurt = (amount as f32 * falloff)
+                            .ceil()
+                            as u16;
+                        if hurt > 0 {
+                            targets.push((point, hurt));
+                        }
+                    }
+                }
+            }
+            targets
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    fn hurt(
+        targets: &[(Vec3<i32>, u16)],
+        point: Vec3<i32>,
+    ) -> u16 {
+        targets
+            .iter()
+            .find(|(p, _)| *p == point)
+            .map_or(0, |(_, amount)| *amount)
+    }
+
+    #[test]
+    fn ray_hurts_first_tile() {
+        let mut area = Area::new(5, 1, 1);
+        area.set_tile(Vec3::new(2, 0, 0), Tile::Crate);
+        area.set_tile(Vec3::new(3, 0, 0), Tile::Crate);
+        let ray = Damage::Ray {
+            origin: Vec3::zero(),
+            direction: Vec3::unit_x(),
+            reach: 10.0,
+            amount: 3,
+        };
+        assert_eq!(
+            targets(&area, &ray),
+            vec![(Vec3::new(2, 0, 0), 3)]
+        );
+
+        let short = Damage::Ray {
+            origin: Vec3::zero(),
+            direction: Vec3::unit_x(),
+            reach: 1.0,
+            amount: 3,
+        };
+        assert!(targets(&area, &short).is_empty());
+    }
+
+    #[test]
+    fn explosion_falls_off() {
+        let area = Area::new(7, 7, 7);
+        let explosion = Damage::Explosion {
+            center: Vec3::broadcast(3.0),
+            radius: 2.0,
+            amount: 10,
+        };
+        let targets = targets(&area, &explosion);
+        assert_eq!(hurt(&targets, Vec3::broadcast(3)), 10);
+        assert_eq!(hurt(&targets, Vec3::new(4, 3, 3)), 5);
+        // Right on the edge there's nothing left.
+        assert_eq!(hurt(&targets, Vec3::new(5, 3, 3)), 0);
+        assert_eq!(hurt(&targets, Vec3::new(5, 4, 3)), 0);
+    }
+
+    #[test]
+    fn explosion_hurts_at_least_one() {
+        let area = Area::new(3, 1, 1);
+        let explosion = Damage::Explosion {
+            center: Vec3::zero(),
+            radius: 1.1,
+            amount: 1,
+        };
+        let targets = targets(&area, &explosion);
+        // 1 * (1 - 1 / 1.1) rounds up instead of down to 0.
+        assert_eq!(hurt(&targets, Vec3::new(1, 0, 0)), 1);
+    }
+
+    #[test]
+    fn tiles_break_into_debris() {
+        let mut area = Area::new(3, 1, 1);
+        let cracked = Vec3::new(1, 0, 0);
+        area.set_tile(cracked, Tile::CrackedWall);
+        let explosion = Damage::Explosion {
+            center: Vec3::new(1.0, 0.0, 0.0),
+            radius: 1.0,
+            amount: 20,
+        };
+
+        assert!(area.apply_damage(&explosion).is_empty());
+        assert_eq!(area.hit_points(cracked), Some(10));
+        assert_eq!(
+            area.apply_damage(&explosion),
+            vec![cracked]
+        );
+        assert_eq!(
+            area.tile_at(cracked),
+            Some(Tile::CrackedWall.debris())
+        );
+        assert_eq!(area.hit_points(cracked), None);
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use vek::Vec3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MarkerKind {
+    PlayerStart,
+    Enemy(String),
+    Item(String),
+    Light {
+        radius: f32,
+        color: (f32, f32, f32),
+    },
+    /// A box centered on the marker that fires when entered.
+    Trigger {
+        name: String,
+        size: (f32, f32, f32),
+    },
+}
+
+/// Something placed in the map that gets spawned as an entity
+/// when the level starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub position: Vec3<f32>,
+    /// Degrees clockwise from north.
+    pub facing: f32,
+}
+
+impl Marker {
+    pub fn new(
+        kind: MarkerKind,
+        position: Vec3<f32>,
+    ) -> Marker {
+        Marker {
+            kind,
+            position,
+            facing: 0.0,
+        }
+    }
+
+    pub fn facing(mut self, degrees: f32) -> Marker {
+        self.facing = degrees;
+        self
+    }
+
+    /// The facing as a rotation around y, counter clockwise
+    /// from north in radians.
+    pub fn yaw(&self) -> f32 {
+        -self.facing.to_radians()
+    }
+}
+
+/// Facing given by name in map files, in degrees.
+pub fn facing_from_name(name: &str) -> Option<f32> {
+    match name {
+        "north" => Some(0.0),
+        "east" => Some(90.0),
+        "south" => Some(180.0),
+        "west" => Some(270.0),
+        _ => None,
+    }
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::Tile;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Face {
+    Top,
+    Bottom,
+    Side,
+}
+
+impl Face {
+    pub fn from_name(name: &str) -> Option<Face> {
+        match name {
+            "top" => Some(Face::Top),
+            "bottom" => Some(Face::Bottom),
+            "side" => Some(Face::Side),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Face::Top => "top",
+            Face::Bottom => "bottom",
+            Face::Side => "side",
+        }
+    }
+}
+
+/// Which texture goes on each face of each tile kind.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Materials {
+    table: HashMap<(Tile, Face), String>,
+}
+
+impl Materials {
+    pub fn new() -> Materials {
+        Materials::default()
+    }
+
+    pub fn get(
+        &self,
+        tile: Tile,
+        face: Face,
+    ) -> Option<&str> {
+        self.table.get(&(tile, face)).map(|t| t.as_str())
+    }
+
+    /// Returns the texture that was already set for that
+    /// face, if any.
+    pub fn set(
+        &mut self,
+        tile: Tile,
+        face: Face,
+        texture: String,
+    ) -> Option<String> {
+        self.table.insert((tile, face), texture)
+    }
+
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (Tile, Face, &str)> + '_ {
+        self.table.iter().map(|((tile, face), texture)| {
+            (*tile, *face, texture.as_str())
+        })
+    }
+
+    /// Every texture used by the table, without repeats.
+    pub fn textures(&self) -> Vec<&str> {
+        let mut textures: Vec<&str> =
+            self.table.values().map(|t| t.as_str()).collect();
+        textures.sort();
+        textures.dedup();
+        textures
+    }
+}
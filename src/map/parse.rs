@@ -0,0 +1,736 @@
+use std::{
+    collections::HashMap, convert::TryFrom, fmt, str::FromStr,
+};
+
+use vek::{Aabb, Vec3};
+
+use super::{
+    marker::facing_from_name, Area, Face, Marker, MarkerKind,
+    Materials, Tile,
+};
+
+/// Error returned when a map file can't be turned into an
+/// `Area`.
+#[derive(Debug)]
+pub enum MapError {
+    Io(String),
+    /// A binary or RON file that can't be decoded.
+    Format(String),
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl MapError {
+    fn at(
+        line: usize,
+        column: usize,
+        message: String,
+    ) -> MapError {
+        MapError::Parse {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Io(message) |
+            MapError::Format(message) => {
+                write!(f, "{}", message)
+            }
+            MapError::Parse {
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "line {}, column {}: {}",
+                line, column, message
+            ),
+        }
+    }
+}
+
+enum Section {
+    Header,
+    Legend,
+    Layer,
+}
+
+struct Layer {
+    line: usize,
+    rows: Vec<Vec<Tile>>,
+    /// Line and column of the first tile of each row.
+    starts: Vec<(usize, usize)>,
+}
+
+/// Where the markers and tiles of an `Area` are in the map
+/// file it was parsed from, as lines and columns.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceMap {
+    origin: Vec3<i32>,
+    markers: Vec<(usize, usize)>,
+    rows: Vec<Vec<(usize, usize)>>,
+}
+
+impl SourceMap {
+    /// Where the marker at `index` in `Area::markers` is.
+    pub fn marker(
+        &self,
+        index: usize,
+    ) -> Option<(usize, usize)> {
+        self.markers.get(index).copied()
+    }
+
+    /// The character that made the tile at `point`.
+    pub fn tile(
+        &self,
+        point: Vec3<i32>,
+    ) -> Option<(usize, usize)> {
+        let local = point - self.origin;
+        if local.x < 0 || local.y < 0 || local.z < 0 {
+            return None;
+        }
+        let (line, column) = *self
+            .rows
+            .get(local.y as usize)?
+            .get(local.z as usize)?;
+        Some((line, column + local.x as usize))
+    }
+}
+
+/// Parses the text map format.
+///
+/// ```text
+/// // Comments and blank lines are ignored.
+/// size <width> <depth> <height>
+/// origin <x> <y> <z>
+///
+/// player <x> <y> <z> [facing]
+/// enemy <kind> <x> <y> <z> [facing]
+/// item <kind> <x> <y> <z>
+/// light <x> <y> <z> <radius> [<r> <g> <b>]
+/// trigger <name> <x> <y> <z> <width> <height> <depth>
+///
+/// legend
+/// # wall side=assets/stone_wall_b.png top=assets/stone_floor_c.png
+/// . air
+///
+/// layer
+/// ####
+/// #..#
+/// ####
+/// ```
+///
+/// Legend entries can give the tile kind a texture for its
+/// `top`, `bottom` and `side` faces. There is one `layer` per
+/// floor, from the ground up, each one made of `depth` rows
+/// of `width` legend characters. The optional `origin` is
+/// where the first tile of the first layer goes, `0 0 0` if
+/// missing.
+///
+/// Markers place the entities of the level. Facings are in
+/// degrees clockwise from north or one of `north`, `east`,
+/// `south` and `west`, north when missing. Light colors go
+/// from 0 to 1, white when missing.
+pub fn parse(
+    src: &str,
+) -> Result<(Area, SourceMap), MapError> {
+    let mut size: Option<(usize, usize, usize)> = None;
+    let mut origin: Option<Vec3<i32>> = None;
+    // Where the header the bounds come from is, for when
+    // they don't fit.
+    let mut bounds_at = (1, 1);
+    let mut legend: HashMap<char, Tile> = HashMap::new();
+    let mut materials = Materials::new();
+    let mut markers = Vec::new();
+    let mut marker_starts = Vec::new();
+    let mut layers: Vec<Layer> = Vec::new();
+    let mut section = Section::Header;
+    let mut last_line = 0;
+
+    for (i, raw) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw.trim();
+        let column = raw
+            .chars()
+            .take_while(|ch| ch.is_whitespace())
+            .count() +
+            1;
+        last_line = line_no;
+
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        match line.split_whitespace().next() {
+            Some("size") => {
+                if size.is_some() {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        "size declared twice".to_string(),
+                    ));
+                }
+                size = Some(parse_size(line_no, raw)?);
+                if origin.is_none() {
+                    bounds_at = (line_no, column);
+                }
+                section = Section::Header;
+                continue;
+            }
+            Some("origin") => {
+                if origin.is_some() {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        "origin declared twice".to_string(),
+                    ));
+                }
+                origin = Some(parse_origin(line_no, raw)?);
+                bounds_at = (line_no, column);
+                section = Section::Header;
+                continue;
+            }
+            Some("player") | Some("enemy") |
+            Some("item") | Some("light") |
+            Some("trigger") => {
+                markers.push(parse_marker(line_no, raw)?);
+                marker_starts.push((line_no, column));
+                section = Section::Header;
+                continue;
+            }
+            Some("legend") => {
+                if !layers.is_empty() {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        "legend must come before the layers"
+                            .to_string(),
+                    ));
+                }
+                section = Section::Legend;
+                continue;
+            }
+            Some("layer") => {
+                if size.is_none() {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        "layer found before the size header"
+                            .to_string(),
+                    ));
+                }
+                layers.push(Layer {
+                    line: line_no,
+                    rows: Vec::new(),
+                    starts: Vec::new(),
+                });
+                section = Section::Layer;
+                continue;
+            }
+            _ => {}
+        }
+
+        match section {
+            Section::Header => {
+                return Err(MapError::at(
+                    line_no,
+                    column,
+                    format!("unexpected line {:?}", line),
+                ));
+            }
+            Section::Legend => {
+                let (ch, tile) = parse_legend(
+                    line_no,
+                    raw,
+                    &mut materials,
+                )?;
+                if legend.insert(ch, tile).is_some() {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        format!(
+                            "legend character {:?} declared twice",
+                            ch
+                        ),
+                    ));
+                }
+            }
+            Section::Layer => {
+                let (width, depth, _) = size.unwrap();
+                let layer = layers.last_mut().unwrap();
+                if layer.rows.len() == depth {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        format!(
+                            "layer has more than {} rows",
+                            depth
+                        ),
+                    ));
+                }
+
+                let mut row = Vec::with_capacity(width);
+                for (offset, ch) in line.chars().enumerate() {
+                    let tile =
+                        legend.get(&ch).copied().ok_or_else(
+                            || {
+                                MapError::at(
+                                    line_no,
+                                    column + offset,
+                                    format!(
+                                        "unknown tile character {:?}",
+                                        ch
+                                    ),
+                                )
+                            },
+                        )?;
+                    row.push(tile);
+                }
+
+                if row.len() != width {
+                    return Err(MapError::at(
+                        line_no,
+                        column + row.len().min(width),
+                        format!(
+                            "row has {} tiles, expected {}",
+                            row.len(),
+                            width
+                        ),
+                    ));
+                }
+                layer.rows.push(row);
+                layer.starts.push((line_no, column));
+            }
+        }
+    }
+
+    let (width, depth, height) = size.ok_or_else(|| {
+        MapError::at(1, 1, "missing size header".to_string())
+    })?;
+
+    for layer in &layers {
+        if layer.rows.len() != depth {
+            return Err(MapError::at(
+                layer.line,
+                1,
+                format!(
+                    "layer has {} rows, expected {}",
+                    layer.rows.len(),
+                    depth
+                ),
+            ));
+        }
+    }
+
+    if layers.len() != height {
+        return Err(MapError::at(
+            last_line,
+            1,
+            format!(
+                "map has {} layers, expected {}",
+                layers.len(),
+                height
+            ),
+        ));
+    }
+
+    let origin = origin.unwrap_or_else(Vec3::zero);
+    let max = Vec3::new(width, height, depth).map2(
+        origin,
+        |size, min| {
+            i32::try_from(size)
+                .ok()
+                .and_then(|size| min.checked_add(size))
+        },
+    );
+    let max = match (max.x, max.y, max.z) {
+        (Some(x), Some(y), Some(z)) => Vec3::new(x, y, z),
+        _ => {
+            return Err(MapError::at(
+                bounds_at.0,
+                bounds_at.1,
+                "map goes past the largest coordinate"
+                    .to_string(),
+            ))
+        }
+    };
+    let mut area =
+        Area::with_bounds(Aabb { min: origin, max });
+    area.materials = materials;
+    area.markers = markers;
+    let source = SourceMap {
+        origin,
+        markers: marker_starts,
+        rows: layers
+            .iter()
+            .map(|layer| layer.starts.clone())
+            .collect(),
+    };
+    for (y, layer) in layers.into_iter().enumerate() {
+        for (z, row) in layer.rows.into_iter().enumerate() {
+            for (x, tile) in row.into_iter().enumerate() {
+                area.set_tile(
+                    origin +
+                        Vec3::new(x, y, z)
+                            .map(|e| e as i32),
+                    tile,
+                );
+            }
+        }
+    }
+
+    Ok((area, source))
+}
+
+fn parse_size(
+    line_no: usize,
+    raw: &str,
+) -> Result<(usize, usize, usize), MapError> {
+    let mut dims = Vec::new();
+    for (column, word) in words(raw).skip(1) {
+        let dim = word.parse::<usize>().map_err(|_| {
+            MapError::at(
+                line_no,
+                column,
+                format!("invalid dimension {:?}", word),
+            )
+        })?;
+        if dim == 0 {
+            return Err(MapError::at(
+                line_no,
+                column,
+                "dimensions must be greater than zero"
+                    .to_string(),
+            ));
+        }
+        dims.push(dim);
+    }
+
+    match dims.as_slice() {
+        &[width, depth, height] => Ok((width, depth, height)),
+        _ => Err(MapError::at(
+            line_no,
+            1,
+            "size expects <width> <depth> <height>"
+                .to_string(),
+        )),
+    }
+}
+
+fn parse_origin(
+    line_no: usize,
+    raw: &str,
+) -> Result<Vec3<i32>, MapError> {
+    let mut coords = Vec::new();
+    for (column, word) in words(raw).skip(1) {
+        let coord = word.parse::<i32>().map_err(|_| {
+            MapError::at(
+                line_no,
+                column,
+                format!("invalid coordinate {:?}", word),
+            )
+        })?;
+        coords.push(coord);
+    }
+
+    match coords.as_slice() {
+        &[x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(MapError::at(
+            line_no,
+            1,
+            "origin expects <x> <y> <z>".to_string(),
+        )),
+    }
+}
+
+fn parse_marker(
+    line_no: usize,
+    raw: &str,
+) -> Result<Marker, MapError> {
+    let mut fields = Fields::new(line_no, raw);
+    let keyword = fields.word("marker")?;
+    let marker = match keyword {
+        "player" => {
+            let position = fields.position()?;
+            Marker::new(MarkerKind::PlayerStart, position)
+                .facing(fields.facing()?)
+        }
+        "enemy" => {
+            let kind = fields.word("enemy kind")?.to_string();
+            let position = fields.position()?;
+            Marker::new(MarkerKind::Enemy(kind), position)
+                .facing(fields.facing()?)
+        }
+        "item" => {
+            let kind = fields.word("item kind")?.to_string();
+            Marker::new(
+                MarkerKind::Item(kind),
+                fields.position()?,
+            )
+        }
+        "light" => {
+            let position = fields.position()?;
+            let radius = fields.number("radius")?;
+            let color = if fields.is_empty() {
+                (1.0, 1.0, 1.0)
+            } else {
+                (
+                    fields.number("red")?,
+                    fields.number("green")?,
+                    fields.number("blue")?,
+                )
+            };
+            Marker::new(
+                MarkerKind::Light { radius, color },
+                position,
+            )
+        }
+        _ => {
+            let name =
+                fields.word("trigger name")?.to_string();
+            let position = fields.position()?;
+            let size = (
+                fields.number("width")?,
+                fields.number("height")?,
+                fields.number("depth")?,
+            );
+            Marker::new(
+                MarkerKind::Trigger { name, size },
+                position,
+            )
+        }
+    };
+    fields.end()?;
+    Ok(marker)
+}
+
+/// Reads the words of a line one at a time, reporting the
+/// column of whichever is wrong or missing.
+struct Fields<'a> {
+    line_no: usize,
+    /// Column right after the last word.
+    end: usize,
+    words: Vec<(usize, &'a str)>,
+    next: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line_no: usize, raw: &'a str) -> Fields<'a> {
+        Fields {
+            line_no,
+            end: raw.trim_end().chars().count() + 1,
+            words: words(raw).collect(),
+            next: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let word = self.words.get(self.next).copied();
+        self.next += 1;
+        word
+    }
+
+    fn is_empty(&self) -> bool {
+        self.next >= self.words.len()
+    }
+
+    fn word(
+        &mut self,
+        what: &str,
+    ) -> Result<&'a str, MapError> {
+        match self.next() {
+            Some((_, word)) => Ok(word),
+            None => Err(MapError::at(
+                self.line_no,
+                self.end,
+                format!("missing {}", what),
+            )),
+        }
+    }
+
+    fn number<T: FromStr>(
+        &mut self,
+        what: &str,
+    ) -> Result<T, MapError> {
+        let (column, word) = match self.next() {
+            Some(next) => next,
+            None => {
+                return Err(MapError::at(
+                    self.line_no,
+                    self.end,
+                    format!("missing {}", what),
+                ))
+            }
+        };
+        word.parse().map_err(|_| {
+            MapError::at(
+                self.line_no,
+                column,
+                format!("invalid {} {:?}", what, word),
+            )
+        })
+    }
+
+    fn position(&mut self) -> Result<Vec3<f32>, MapError> {
+        Ok(Vec3::new(
+            self.number("x coordinate")?,
+            self.number("y coordinate")?,
+            self.number("z coordinate")?,
+        ))
+    }
+
+    /// An optional facing, north if missing.
+    fn facing(&mut self) -> Result<f32, MapError> {
+        let (column, word) = match self.next() {
+            Some(next) => next,
+            None => return Ok(0.0),
+        };
+        facing_from_name(word)
+            .or_else(|| word.parse().ok())
+            .ok_or_else(|| {
+                MapError::at(
+                    self.line_no,
+                    column,
+                    format!("invalid facing {:?}", word),
+                )
+            })
+    }
+
+    fn end(&mut self) -> Result<(), MapError> {
+        match self.next() {
+            Some((column, word)) => Err(MapError::at(
+                self.line_no,
+                column,
+                format!("unexpected {:?}", word),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse_legend(
+    line_no: usize,
+    raw: &str,
+    materials: &mut Materials,
+) -> Result<(char, Tile), MapError> {
+    let mut words = words(raw);
+    let (column, symbol) = words.next().unwrap();
+    let mut chars = symbol.chars();
+    let ch = match (chars.next(), chars.next()) {
+        (Some(ch), None) => ch,
+        _ => {
+            return Err(MapError::at(
+                line_no,
+                column,
+                format!(
+                    "legend symbol {:?} must be a single character",
+                    symbol
+                ),
+            ))
+        }
+    };
+
+    let (column, name) = words.next().ok_or_else(|| {
+        MapError::at(
+            line_no,
+            column + 1,
+            format!("missing tile name for {:?}", ch),
+        )
+    })?;
+    let tile = Tile::from_name(name).ok_or_else(|| {
+        MapError::at(
+            line_no,
+            column,
+            format!("unknown tile kind {:?}", name),
+        )
+    })?;
+
+    for (column, word) in words {
+        let mut parts = word.splitn(2, '=');
+        let key = parts.next().unwrap();
+        let texture = parts.next().filter(|t| !t.is_empty());
+        let (face, texture) =
+            match (Face::from_name(key), texture) {
+                (Some(face), Some(texture)) => {
+                    (face, texture)
+                }
+                _ => {
+                    return Err(MapError::at(
+                        line_no,
+                        column,
+                        format!(
+                    "expected <top|bottom|side>=<texture>, \
+                             found {:?}",
+                    word
+                ),
+                    ))
+                }
+            };
+
+        let texture = texture.to_string();
+        match materials.set(tile, face, texture.clone()) {
+            Some(previous) if previous != texture => {
+                return Err(MapError::at(
+                    line_no,
+                    column,
+                    format!(
+                        "{} {} face already uses {:?}",
+                        name, key, previous
+                    ),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok((ch, tile))
+}
+
+/// Splits a line on whitespace, keeping the 1-based column
+/// each word starts at.
+fn words(raw: &str) -> impl Iterator<Item = (usize, &str)> {
+    raw.split_whitespace().map(move |word| {
+        let offset =
+            word.as_ptr() as usize - raw.as_ptr() as usize;
+        (raw[..offset].chars().count() + 1, word)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = "size 2 1 1\n\
+                       origin 1 0 0\n\
+                       legend\n\
+                       # wall\n\
+                       layer\n\
+                       ##\n";
+
+    #[test]
+    fn origin_moves_bounds() {
+        let area = Area::parse(MAP).unwrap();
+        assert_eq!(area.bounds().min, Vec3::new(1, 0, 0));
+        assert_eq!(area.bounds().max, Vec3::new(3, 1, 1));
+    }
+
+    #[test]
+    fn bounds_past_largest_coordinate() {
+        let src =
+            MAP.replace("origin 1", "origin 2147483647");
+        match Area::parse(&src) {
+            Err(MapError::Parse { line, column, .. }) => {
+                assert_eq!((line, column), (2, 1))
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use vek::{Aabb, Vec3};
+
+use super::Area;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub tile: Vec3<i32>,
+    /// Normal of the face that was hit, zero if the ray
+    /// started inside the tile.
+    pub normal: Vec3<i32>,
+    pub point: Vec3<f32>,
+    pub distance: f32,
+}
+
+/// Walks the grid one tile at a time (Amanatides & Woo) and
+/// checks the ray against the collision shapes of every tile
+/// it goes through.
+pub fn raycast(
+    area: &Area,
+    origin: Vec3<f32>,
+    direction: Vec3<f32>,
+    max_distance: f32,
+) -> Option<RayHit> {
+    let dir = direction.try_normalized()?;
+    let bounds = area.bounds();
+
+    // Tiles are centered on integer coordinates, shifting by
+    // half a tile lines them up with the floor of the
+    // position.
+    let shifted = origin + 0.5;
+    let mut cell = shifted.map(|e| e.floor() as i32);
+    let step = dir.map(|e| {
+        if e > 0.0 {
+            1
+        } else if e < 0.0 {
+            -1
+        } else {
+            0
+        }
+    });
+    let t_delta = dir.map(|e| {
+        if e != 0.0 {
+            1.0 / e.abs()
+        } else {
+            std::f32::INFINITY
+        }
+    });
+    let mut t_max = Vec3::new(0, 1, 2).map(|axis| {
+        let (d, p, c) =
+            (dir[axis], shifted[axis], cell[axis]);
+        if d > 0.0 {
+            (c as f32 + 1.0 - p) / d
+        } else if d < 0.0 {
+            (p - c as f32) / -d
+        } else {
+            std::f32::INFINITY
+        }
+    });
+
+    loop {
+        if let Some(tile) = area.tile_at(cell) {
+            let center = cell.map(|e| e as f32);
+            let nearest = tile
+                .collision()
+                .iter()
+                .filter_map(|shape| {
+                    intersect(
+                        origin,
+                        dir,
+                        Aabb {
+                            min: center + shape.min,
+                            max: center + shape.max,
+                        },
+                    )
+                })
+                .min_by(|a, b| {
+                    a.0.partial_cmp(&b.0).unwrap()
+                });
+
+            if let Some((distance, normal)) = nearest {
+                if distance > max_distance {
+                    return None;
+                }
+                return Some(RayHit {
+                    tile: cell,
+                    normal,
+                    point: origin + dir * distance,
+                    distance,
+                });
+            }
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_distance {
+            return None;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        // Nothing left to hit once the ray is out of bounds
+        // and moving away from them.
+        let leaving =
+            (0..3).any(|axis| {
+                (cell[axis] < bounds.min[axis] &&
+                    step[axis] <= 0) ||
+                    (cell[axis] >= bounds.max[axis] &&
+                        step[axis] >= 0)
+            });
+        if leaving {
+            return None;
+        }
+    }
+}
+
+/// Slab test, returns the distance to the box and the normal
+/// of the face the ray enters through.
+fn intersect(
+    origin: Vec3<f32>,
+    dir: Vec3<f32>,
+    aabb: Aabb<f32>,
+) -> Option<(f32, Vec3<i32>)> {
+    let mut t_near = std::f32::NEG_INFINITY;
+    let mut t_far = std::f32::INFINITY;
+    let mut normal = Vec3::zero();
+
+    for axis in 0..3 {
+        let (o, d) = (origin[axis], dir[axis]);
+        let (min, max) = (aabb.min[axis], aabb.max[axis]);
+        if d == 0.0 {
+            if o < min || o > max {
+                return None;
+            }
+            continue;
+        }
+
+        let (t1, t2) = ((min - o) / d, (max - o) / d);
+        let (enter, exit) =
+            if t1 < t2 { (t1, t2) } else { (t2, t1) };
+        if enter > t_near {
+            t_near = enter;
+            normal = Vec3::zero();
+            normal[axis] = if d > 0.0 { -1 } else { 1 };
+        }
+        t_far = t_far.min(exit);
+    }
+
+    if t_near > t_far || t_far < 0.0 {
+        return None;
+    }
+    if t_near < 0.0 {
+        return Some((0.0, Vec3::zero()));
+    }
+    Some((t_near, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    fn forward(
+        area: &Area,
+        origin: Vec3<f32>,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        raycast(area, origin, Vec3::unit_x(), max_distance)
+    }
+
+    #[test]
+    fn hits_nearest_face() {
+        let mut area = Area::new(10, 10, 10);
+        area.set_tile(Vec3::new(5, 0, 0), Tile::Wall);
+        area.set_tile(Vec3::new(7, 0, 0), Tile::Wall);
+
+        let hit =
+            forward(&area, Vec3::zero(), 100.0).unwrap();
+        assert_eq!(hit.tile, Vec3::new(5, 0, 0));
+        assert_eq!(hit.normal, Vec3::new(-1, 0, 0));
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        assert!((hit.point.x - 4.5).abs() < 1e-5);
+
+        assert_eq!(forward(&area, Vec3::zero(), 4.0), None);
+        let back = raycast(
+            &area,
+            Vec3::zero(),
+            -Vec3::unit_x(),
+            std::f32::INFINITY,
+        );
+        assert_eq!(back, None);
+    }
+
+    #[test]
+    fn normals() {
+        let mut area = Area::new(10, 10, 10);
+        for x in 0..10 {
+            for z in 0..10 {
+                area.set_tile(Vec3::new(x, 0, z), Tile::Wall);
+            }
+        }
+        area.set_tile(Vec3::new(5, 5, 5), Tile::Wall);
+
+        let down = raycast(
+            &area,
+            Vec3::new(2.0, 3.0, 2.0),
+            Vec3::new(1.0, -1.0, 0.3),
+            100.0,
+        )
+        .unwrap();
+        assert_eq!(down.normal, Vec3::unit_y());
+        assert!((down.point.y - 0.5).abs() < 1e-5);
+
+        let sides = [
+            (Vec3::unit_x(), Vec3::new(-1, 0, 0)),
+            (-Vec3::unit_x(), Vec3::new(1, 0, 0)),
+            (Vec3::unit_z(), Vec3::new(0, 0, -1)),
+            (-Vec3::unit_z(), Vec3::new(0, 0, 1)),
+            (Vec3::unit_y(), Vec3::new(0, -1, 0)),
+        ];
+        for &(dir, normal) in &sides {
+            let origin = Vec3::broadcast(5.0) - dir * 3.0;
+            let hit =
+                raycast(&area, origin, dir, 100.0).unwrap();
+            assert_eq!(hit.tile, Vec3::new(5, 5, 5));
+            assert_eq!(hit.normal, normal, "{:?}", dir);
+        }
+
+        let inside = raycast(
+            &area,
+            Vec3::broadcast(5.0),
+            Vec3::unit_x(),
+            100.0,
+        )
+        .unwrap();
+        assert_eq!(inside.normal, Vec3::zero());
+        assert_eq!(inside.distance, 0.0);
+    }
+
+    #[test]
+    fn partial_shapes() {
+        let mut area = Area::new(10, 10, 10);
+        area.set_tile(Vec3::new(3, 1, 5), Tile::Pillar);
+
+        let beside =
+            forward(&area, Vec3::new(0.0, 1.0, 5.4), 100.0);
+        assert_eq!(beside, None);
+        let hit =
+            forward(&area, Vec3::new(0.0, 1.0, 5.1), 100.0)
+                .unwrap();
+        assert_eq!(hit.tile, Vec3::new(3, 1, 5));
+        assert!((hit.distance - 2.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn starts_outside_bounds() {
+        let mut area = Area::new(10, 10, 10);
+        area.set_tile(Vec3::new(0, 0, 3), Tile::Wall);
+
+        let hit = forward(
+            &area,
+            Vec3::new(-20.0, 0.0, 3.0),
+            std::f32::INFINITY,
+        )
+        .unwrap();
+        assert_eq!(hit.tile, Vec3::new(0, 0, 3));
+        assert_eq!(hit.normal, Vec3::new(-1, 0, 0));
+        assert!((hit.distance - 19.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,577 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+use vek::{Aabb, Vec3};
+
+use super::{
+    Area, Face, Facing, MapError, Marker, MarkerKind,
+    Materials, Tile,
+};
+
+const MAGIC: &[u8; 4] = b"AREA";
+const VERSION: u16 = 3;
+
+/// Most tiles a stored area can have, so a corrupt size can't
+/// make loading allocate without end.
+const MAX_VOLUME: u64 = 1 << 24;
+
+/// What gets written to disk, shared by the binary and the
+/// RON formats. Tiles are stored as `(count, tile)` runs in
+/// the same order as `Area::points`, markers as `(kind,
+/// position, facing)`.
+#[derive(Debug, Serialize, Deserialize)]
+struct AreaData {
+    version: u16,
+    origin: (i32, i32, i32),
+    size: (u32, u32, u32),
+    tiles: Vec<(u32, Tile)>,
+    materials: Vec<(Tile, Face, String)>,
+    markers: Vec<(MarkerKind, (f32, f32, f32), f32)>,
+}
+
+impl AreaData {
+    fn from_area(area: &Area) -> AreaData {
+        let mut tiles: Vec<(u32, Tile)> = Vec::new();
+        for (_, tile) in area.iter() {
+            match tiles.last_mut() {
+                Some((count, last)) if *last == tile => {
+                    *count += 1
+                }
+                _ => tiles.push((1, tile)),
+            }
+        }
+
+        let mut materials: Vec<(Tile, Face, String)> = area
+            .materials
+            .iter()
+            .map(|(tile, face, texture)| {
+                (tile, face, texture.to_string())
+            })
+            .collect();
+        materials.sort();
+
+        let origin = area.bounds().min;
+        AreaData {
+            version: VERSION,
+            origin: (origin.x, origin.y, origin.z),
+            size: (
+                area.width() as u32,
+                area.depth() as u32,
+                area.height() as u32,
+            ),
+            tiles,
+            materials,
+            markers: area
+                .markers
+                .iter()
+                .map(|m| {
+                    let p = m.position;
+                    (
+                        m.kind.clone(),
+                        (p.x, p.y, p.z),
+                        m.facing,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn into_area(self) -> Result<Area, MapError> {
+        if self.version != VERSION {
+            return Err(MapError::Format(format!(
+                "unsupported version {}, expected {}",
+                self.version, VERSION
+            )));
+        }
+
+        let (x, y, z) = self.origin;
+        let (width, depth, height) = self.size;
+        let origin = Vec3::new(x, y, z);
+        let max = Vec3::new(width, height, depth).map2(
+            origin,
+            |size, min| {
+                min.checked_add(i32::try_from(size).ok()?)
+            },
+        );
+        let max = match (max.x, max.y, max.z) {
+            (Some(x), Some(y), Some(z)) => Vec3::new(x, y, z),
+            _ => {
+                return Err(MapError::Format(format!(
+                    "size {} {} {} is out of range",
+                    width, depth, height
+                )))
+            }
+        };
+
+        let volume =
+            width as u64 * depth as u64 * height as u64;
+        if volume > MAX_VOLUME {
+            return Err(MapError::Format(format!(
+                "{} tiles is more than the {} allowed",
+                volume, MAX_VOLUME
+            )));
+        }
+        if self.tiles.len() as u64 > volume {
+            return Err(MapError::Format(format!(
+                "{} tile runs for {} tiles",
+                self.tiles.len(),
+                volume
+            )));
+        }
+        let found: u64 = self
+            .tiles
+            .iter()
+            .map(|(count, _)| *count as u64)
+            .sum();
+        if found != volume {
+            return Err(MapError::Format(format!(
+                "expected {} tiles, found {}",
+                volume, found
+            )));
+        }
+
+        let mut area =
+            Area::with_bounds(Aabb { min: origin, max });
+        let tiles = self.tiles.into_iter().flat_map(
+            |(count, tile)| {
+                std::iter::repeat(tile).take(count as usize)
+            },
+        );
+        let points: Vec<Vec3<i32>> = area.points().collect();
+        for (point, tile) in points.into_iter().zip(tiles) {
+            area.set_tile(point, tile);
+        }
+
+        let mut materials = Materials::new();
+        for (tile, face, texture) in self.materials {
+            materials.set(tile, face, texture);
+        }
+        area.materials = materials;
+        area.markers = self
+            .markers
+            .into_iter()
+            .map(|(kind, (x, y, z), facing)| {
+                Marker::new(kind, Vec3::new(x, y, z))
+                    .facing(facing)
+            })
+            .collect();
+
+        Ok(area)
+    }
+}
+
+pub fn to_ron(area: &Area) -> String {
+    ron::ser::to_string_pretty(
+        &AreaData::from_area(area),
+        ron::ser::PrettyConfig::default(),
+    )
+    .expect("area data is always serializable")
+}
+
+pub fn from_ron(src: &str) -> Result<Area, MapError> {
+    ron::de::from_str::<AreaData>(src)
+        .map_err(|e| MapError::Format(e.to_string()))?
+        .into_area()
+}
+
+/// The binary format is little endian: the `AREA` magic and a
+/// version header, the origin and size, the tile runs, the
+/// materials and the markers, every list prefixed by its
+/// length.
+pub fn to_bytes(area: &Area) -> Vec<u8> {
+    let data = AreaData::from_area(area);
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&data.version.to_le_bytes());
+
+    let (x, y, z) = data.origin;
+    for coord in &[x, y, z] {
+        out.extend_from_slice(&coord.to_le_bytes());
+    }
+    let (width, depth, height) = data.size;
+    for dim in &[width, depth, height] {
+        out.extend_from_slice(&dim.to_le_bytes());
+    }
+
+    write_len(&mut out, data.tiles.len());
+    for (count, tile) in &data.tiles {
+        out.extend_from_slice(&count.to_le_bytes());
+        out.push(tile_code(*tile));
+    }
+
+    write_len(&mut out, data.materials.len());
+    for (tile, face, texture) in &data.materials {
+        out.push(tile_code(*tile));
+        out.push(face_code(*face));
+        write_string(&mut out, texture);
+    }
+
+    write_len(&mut out, data.markers.len());
+    for (kind, (x, y, z), facing) in &data.markers {
+        write_marker_kind(&mut out, kind);
+        for v in &[x, y, z, facing] {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    out
+}
+
+pub fn from_bytes(bytes: &[u8]) -> Result<Area, MapError> {
+    let mut reader = Reader { bytes, at: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(MapError::Format(
+            "not an area file".to_string(),
+        ));
+    }
+
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(MapError::Format(format!(
+            "unsupported version {}, expected {}",
+            version, VERSION
+        )));
+    }
+    let origin =
+        (reader.i32()?, reader.i32()?, reader.i32()?);
+    let size = (reader.u32()?, reader.u32()?, reader.u32()?);
+
+    let mut tiles = Vec::new();
+    for _ in 0..reader.u32()? {
+        let count = reader.u32()?;
+        tiles.push((count, reader.tile()?));
+    }
+
+    let mut materials = Vec::new();
+    for _ in 0..reader.u32()? {
+        let tile = reader.tile()?;
+        let face = reader.face()?;
+        materials.push((tile, face, reader.string()?));
+    }
+
+    let mut markers = Vec::new();
+    for _ in 0..reader.u32()? {
+        markers.push((
+            reader.marker_kind()?,
+            (reader.f32()?, reader.f32()?, reader.f32()?),
+            reader.f32()?,
+        ));
+    }
+
+    if reader.at != bytes.len() {
+        return Err(MapError::Format(format!(
+            "{} unexpected bytes at the end",
+            bytes.len() - reader.at
+        )));
+    }
+
+    AreaData {
+        version,
+        origin,
+        size,
+        tiles,
+        materials,
+        markers,
+    }
+    .into_area()
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, string: &str) {
+    write_len(out, string.len());
+    out.extend_from_slice(string.as_bytes());
+}
+
+fn write_f32s(out: &mut Vec<u8>, values: &[f32]) {
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// A code for the kind followed by whatever it carries.
+fn write_marker_kind(out: &mut Vec<u8>, kind: &MarkerKind) {
+    match kind {
+        MarkerKind::PlayerStart => out.push(0),
+        MarkerKind::Enemy(kind) => {
+            out.push(1);
+            write_string(out, kind);
+        }
+        MarkerKind::Item(kind) => {
+            out.push(2);
+            write_string(out, kind);
+        }
+        MarkerKind::Light { radius, color } => {
+            out.push(3);
+            write_f32s(
+                out,
+                &[*radius, color.0, color.1, color.2],
+            );
+        }
+        MarkerKind::Trigger { name, size } => {
+            out.push(4);
+            write_string(out, name);
+            write_f32s(out, &[size.0, size.1, size.2]);
+        }
+    }
+}
+
+fn tile_code(tile: Tile) -> u8 {
+    match tile {
+        Tile::Air => 0,
+        Tile::Wall => 1,
+        Tile::HalfBlock => 2,
+        Tile::Stairs(Facing::North) => 3,
+        Tile::Stairs(Facing::East) => 4,
+        Tile::Stairs(Facing::South) => 5,
+        Tile::Stairs(Facing::West) => 6,
+        Tile::Pillar => 7,
+        Tile::Water => 8,
+        Tile::Lava => 9,
+        Tile::Grate => 10,
+        Tile::Torch => 11,
+        Tile::Exit => 12,
+        Tile::CrackedWall => 13,
+        Tile::Crate => 14,
+        Tile::Rubble => 15,
+    }
+}
+
+fn tile_from_code(code: u8) -> Option<Tile> {
+    let tile = match code {
+        0 => Tile::Air,
+        1 => Tile::Wall,
+        2 => Tile::HalfBlock,
+        3 => Tile::Stairs(Facing::North),
+        4 => Tile::Stairs(Facing::East),
+        5 => Tile::Stairs(Facing::South),
+        6 => Tile::Stairs(Facing::West),
+        7 => Tile::Pillar,
+        8 => Tile::Water,
+        9 => Tile::Lava,
+        10 => Tile::Grate,
+        11 => Tile::Torch,
+        12 => Tile::Exit,
+        13 => Tile::CrackedWall,
+        14 => Tile::Crate,
+        15 => Tile::Rubble,
+        _ => return None,
+    };
+    Some(tile)
+}
+
+fn face_code(face: Face) -> u8 {
+    match face {
+        Face::Top => 0,
+        Face::Bottom => 1,
+        Face::Side => 2,
+    }
+}
+
+fn face_from_code(code: u8) -> Option<Face> {
+    match code {
+        0 => Some(Face::Top),
+        1 => Some(Face::Bottom),
+        2 => Some(Face::Side),
+        _ => None,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(
+        &mut self,
+        n: usize,
+    ) -> Result<&'a [u8], MapError> {
+        let bytes = self
+            .at
+            .checked_add(n)
+            .and_then(|end| self.bytes.get(self.at..end))
+            .ok_or_else(|| {
+                MapError::Format(format!(
+                    "unexpected end of file at byte {}",
+                    self.at
+                ))
+            })?;
+        self.at += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MapError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, MapError> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    fn u32(&mut self) -> Result<u32, MapError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn i32(&mut self) -> Result<i32, MapError> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn f32(&mut self) -> Result<f32, MapError> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn string(&mut self) -> Result<String, MapError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|e| MapError::Format(e.to_string()))
+    }
+
+    fn tile(&mut self) -> Result<Tile, MapError> {
+        let code = self.u8()?;
+        tile_from_code(code).ok_or_else(|| {
+            MapError::Format(format!("unknown tile {}", code))
+        })
+    }
+
+    fn marker_kind(
+        &mut self,
+    ) -> Result<MarkerKind, MapError> {
+        let kind = match self.u8()? {
+            0 => MarkerKind::PlayerStart,
+            1 => MarkerKind::Enemy(self.string()?),
+            2 => MarkerKind::Item(self.string()?),
+            3 => MarkerKind::Light {
+                radius: self.f32()?,
+                color: (
+                    self.f32()?,
+                    self.f32()?,
+                    self.f32()?,
+                ),
+            },
+            4 => MarkerKind::Trigger {
+                name: self.string()?,
+                size: (self.f32()?, self.f32()?, self.f32()?),
+            },
+            code => {
+                return Err(MapError::Format(format!(
+                    "unknown marker {}",
+                    code
+                )))
+            }
+        };
+        Ok(kind)
+    }
+
+    fn face(&mut self) -> Result<Face, MapError> {
+        let code = self.u8()?;
+        face_from_code(code).ok_or_else(|| {
+            MapError::Format(format!("unknown face {}", code))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debug_map() -> Area {
+        Area::load("assets/maps/debug.map").unwrap()
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let area = debug_map();
+        assert_eq!(
+            from_bytes(&to_bytes(&area)).unwrap(),
+            area
+        );
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let area = debug_map();
+        assert_eq!(from_ron(&to_ron(&area)).unwrap(), area);
+    }
+
+    #[test]
+    fn damaged_round_trip() {
+        let mut area = debug_map();
+        let cracked = Vec3::new(8, 1, 1);
+        assert_eq!(
+            area.tile_at(cracked),
+            Some(Tile::CrackedWall)
+        );
+        area.damage(cracked, 10);
+        area.damage(cracked, 25);
+        assert_eq!(area.tile_at(cracked), Some(Tile::Rubble));
+        let crate_at = Vec3::new(2, 1, 2);
+        area.set_tile(crate_at, Tile::Crate);
+        area.damage(crate_at, 4);
+
+        // The damage is left out, only what broke stays.
+        let loaded = from_bytes(&to_bytes(&area)).unwrap();
+        assert_eq!(loaded, area);
+        assert_eq!(loaded.hit_points(crate_at), Some(10));
+        assert_eq!(from_ron(&to_ron(&area)).unwrap(), area);
+    }
+
+    #[test]
+    fn truncated_bytes() {
+        let bytes = to_bytes(&debug_map());
+        for len in 0..bytes.len() {
+            assert!(
+                from_bytes(&bytes[..len]).is_err(),
+                "{}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_bytes() {
+        let bytes = to_bytes(&debug_map());
+        // The size, right after the magic, version and
+        // origin.
+        let size = MAGIC.len() + 2 + 12;
+        for &dim in &[0x7fff_ffff, 0xffff_ffff, 1 << 20] {
+            let mut corrupt = bytes.clone();
+            corrupt[size..size + 4]
+                .copy_from_slice(&u32::to_le_bytes(dim));
+            assert!(from_bytes(&corrupt).is_err(), "{}", dim);
+        }
+
+        let mut corrupt = bytes.clone();
+        corrupt[size - 4..size]
+            .copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(from_bytes(&corrupt).is_err());
+
+        let mut corrupt = bytes;
+        corrupt.push(0);
+        assert!(from_bytes(&corrupt).is_err());
+    }
+
+    #[test]
+    fn corrupt_ron() {
+        let ron = to_ron(&debug_map());
+        assert!(from_ron(&ron[..ron.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn size_out_of_range() {
+        let area = debug_map();
+        let mut data = AreaData::from_area(&area);
+        data.size.0 = u32::MAX;
+        assert!(data.into_area().is_err());
+
+        let mut data = AreaData::from_area(&area);
+        data.origin.1 = i32::MAX - 1;
+        assert!(data.into_area().is_err());
+    }
+}
@@ -0,0 +1,432 @@
+use vek::{Aabb, Vec3};
+
+use super::Area;
+
+/// How close boxes have to be to count as touching.
+const EPSILON: f32 = 1e-4;
+
+/// The order the axes get moved along, falling and landing
+/// first so walking along the floor doesn't catch on it.
+const AXES: [usize; 3] = [1, 0, 2];
+
+/// How far under a box the ground can be for it to count as
+/// standing on it.
+const GROUND_PROBE: f32 = 0.01;
+
+/// Where a box moved by `Area::sweep` ended up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    /// How far the box got, the motion it was given unless
+    /// it ran into something.
+    pub motion: Vec3<f32>,
+    /// The direction the box was going on the axes it got
+    /// stopped on or ended up touching something, zero on
+    /// the rest. `blocked.y == -1` means it landed on
+    /// something.
+    pub blocked: Vec3<i32>,
+    /// The tiles it was stopped by.
+    pub tiles: Vec<Vec3<i32>>,
+}
+
+/// Moves `aabb` one axis at a time, stopping each axis at the
+/// first tile in the way. Anything between the start and the
+/// end is checked, so fast boxes can't skip over thin tiles,
+/// and what's left of the motion on the other axes still
+/// goes through, sliding the box along what it hit.
+pub fn sweep(
+    area: &Area,
+    aabb: Aabb<f32>,
+    motion: Vec3<f32>,
+) -> Sweep {
+    let mut aabb = aabb;
+    let mut result = Sweep {
+        motion: Vec3::zero(),
+        blocked: Vec3::zero(),
+        tiles: Vec::new(),
+    };
+
+    for &axis in &AXES {
+        let distance = motion[axis];
+        if distance == 0.0 {
+            continue;
+        }
+
+        let mut reach = aabb;
+        if distance > 0.0 {
+            reach.max[axis] += distance;
+        } else {
+            reach.min[axis] += distance;
+        }
+
+        let mut allowed = distance.abs();
+        let mut stopped_by = Vec::new();
+        for (point, shape) in shapes(area, reach) {
+            let others_overlap =
+                (0..3).filter(|a| *a != axis).all(|a| {
+                    shape.min[a] < aabb.max[a] - EPSILON &&
+                        shape.max[a] >
+                            aabb.min[a] + EPSILON
+                });
+            if !others_overlap {
+                continue;
+            }
+            let gap = if distance > 0.0 {
+                shape.min[axis] - aabb.max[axis]
+            } else {
+                aabb.min[axis] - shape.max[axis]
+            };
+            // Shapes the box is already inside of only stop
+            // it going further in, so it can still get back
+            // out but can't sink through them.
+            let gap = if gap < -EPSILON {
+                let ahead = (shape.center()[axis] -
+                    aabb.center()[axis]) *
+                    distance;
+                if ahead <= 0.0 {
+                    continue;
+                }
+                0.0
+            } else {
+                gap.max(0.0)
+            };
+            if gap < allowed {
+                allowed = gap;
+                stopped_by.clear();
+            }
+            if gap <= allowed {
+                stopped_by.push(point);
+            }
+        }
+
+        let moved = allowed * distance.signum();
+        aabb.min[axis] += moved;
+        aabb.max[axis] += moved;
+        result.motion[axis] = moved;
+        if !stopped_by.is_empty() {
+            result.blocked[axis] = distance.signum() as i32;
+            for point in stopped_by {
+                if !result.tiles.contains(&point) {
+                    result.tiles.push(point);
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Like `sweep`, for something walking on the ground. Ledges
+/// up to `step_height` tall are climbed instead of stopping
+/// the box, and walking off one no taller it stays on the
+/// ground instead of falling. Boxes that aren't standing on
+/// anything or are going up move like with `sweep`.
+pub fn walk(
+    area: &Area,
+    aabb: Aabb<f32>,
+    motion: Vec3<f32>,
+    step_height: f32,
+) -> Sweep {
+    let direct = sweep(area, aabb, motion);
+    if !grounded(area, aabb) ||
+        motion.y > 0.0 ||
+        step_height <= 0.0
+    {
+        return direct;
+    }
+
+    let mut result = direct;
+    if result.blocked.x != 0 || result.blocked.z != 0 {
+        if let Some(stepped) =
+            step_up(area, aabb, motion, step_height)
+        {
+            if horizontal(stepped.motion) >
+                horizontal(result.motion) + EPSILON
+            {
+                result = stepped;
+            }
+        }
+    }
+
+    let end = translated(aabb, result.motion);
+    if !grounded(area, end) {
+        let down = sweep(
+            area,
+            end,
+            -Vec3::unit_y() * (step_height + GROUND_PROBE),
+        );
+        if down.blocked.y == -1 {
+            result.motion.y += down.motion.y;
+            result.blocked.y = -1;
+            result.tiles.extend(down.tiles);
+        }
+    }
+
+    result
+}
+
+/// Lifts the box by `step_height`, moves it across and puts
+/// it back down, `None` if there's nothing to land on.
+fn step_up(
+    area: &Area,
+    aabb: Aabb<f32>,
+    motion: Vec3<f32>,
+    step_height: f32,
+) -> Option<Sweep> {
+    let up = sweep(area, aabb, Vec3::unit_y() * step_height);
+    let raised = translated(aabb, up.motion);
+    let across = sweep(
+        area,
+        raised,
+        Vec3::new(motion.x, 0.0, motion.z),
+    );
+    let down = sweep(
+        area,
+        translated(raised, across.motion),
+        -Vec3::unit_y() * up.motion.y,
+    );
+    if down.blocked.y != -1 {
+        return None;
+    }
+
+    let mut tiles = across.tiles;
+    tiles.extend(down.tiles);
+    Some(Sweep {
+        motion: up.motion + across.motion + down.motion,
+        blocked: Vec3::new(
+            across.blocked.x,
+            -1,
+            across.blocked.z,
+        ),
+        tiles,
+    })
+}
+
+/// Whether `aabb` is standing on something.
+fn grounded(area: &Area, aabb: Aabb<f32>) -> bool {
+    sweep(area, aabb, -Vec3::unit_y() * GROUND_PROBE)
+        .blocked
+        .y ==
+        -1
+}
+
+fn translated(aabb: Aabb<f32>, by: Vec3<f32>) -> Aabb<f32> {
+    Aabb {
+        min: aabb.min + by,
+        max: aabb.max + by,
+    }
+}
+
+fn horizontal(motion: Vec3<f32>) -> f32 {
+    Vec3::new(motion.x, 0.0, motion.z).magnitude()
+}
+
+/// The collision shapes of the tiles touching `aabb`, in
+/// world coordinates.
+fn shapes(
+    area: &Area,
+    aabb: Aabb<f32>,
+) -> Vec<(Vec3<i32>, Aabb<f32>)> {
+    // Widened a little, so tiles the box only just touches
+    // on a cell boundary aren't left out.
+    let cells = Aabb {
+        min: aabb.min.map(|e| (e - EPSILON).round() as i32),
+        max: aabb.max.map(|e| (e + EPSILON).round() as i32),
+    };
+    let mut shapes = Vec::new();
+    for y in cells.min.y..=cells.max.y {
+        for z in cells.min.z..=cells.max.z {
+            for x in cells.min.x..=cells.max.x {
+                let point = Vec3::new(x, y, z);
+                let tile = match area.tile_at(point) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let center = point.map(|e| e as f32);
+                for shape in tile.collision() {
+                    shapes.push((
+                        point,
+                        Aabb {
+                            min: center + shape.min,
+                            max: center + shape.max,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+    shapes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    /// A box the size of the player with its feet at `feet`.
+    fn body(feet: Vec3<f32>) -> Aabb<f32> {
+        Aabb {
+            min: feet - Vec3::new(0.25, 0.0, 0.25),
+            max: feet + Vec3::new(0.25, 0.5, 0.25),
+        }
+    }
+
+    fn inside(aabb: Aabb<f32>, shape: Aabb<f32>) -> bool {
+        (0..3).all(|a| {
+            aabb.min[a] < shape.max[a] - EPSILON &&
+                aabb.max[a] > shape.min[a] + EPSILON
+        })
+    }
+
+    #[test]
+    fn falls_onto_floor() {
+        let mut area = Area::new(3, 3, 60);
+        area.set_tile(Vec3::new(1, 0, 1), Tile::Wall);
+
+        let start = body(Vec3::new(1.0, 50.5, 1.0));
+        let result =
+            sweep(&area, start, Vec3::new(0.0, -50.0, 0.0));
+        assert_eq!(result.blocked, Vec3::new(0, -1, 0));
+        assert_eq!(result.tiles, vec![Vec3::new(1, 0, 1)]);
+        let end = translated(start, result.motion);
+        assert!((end.min.y - 0.5).abs() < 1e-4, "{:?}", end);
+    }
+
+    #[test]
+    fn stops_at_ceiling() {
+        let mut area = Area::new(3, 3, 4);
+        area.set_tile(Vec3::new(1, 3, 1), Tile::Wall);
+
+        let start = body(Vec3::new(1.0, 1.0, 1.0));
+        let result =
+            sweep(&area, start, Vec3::new(0.2, 3.0, 0.0));
+        assert_eq!(result.blocked, Vec3::new(0, 1, 0));
+        let end = translated(start, result.motion);
+        assert!((end.max.y - 2.5).abs() < 1e-4, "{:?}", end);
+        assert!((result.motion.x - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn slides_past_corner() {
+        let mut area = Area::new(5, 5, 3);
+        let corner = Vec3::new(2, 1, 2);
+        area.set_tile(corner, Tile::Wall);
+        let shape = Aabb {
+            min: Vec3::broadcast(1.5),
+            max: Vec3::broadcast(2.5),
+        };
+
+        let start = body(Vec3::new(1.0, 1.0, 1.0));
+        let result =
+            sweep(&area, start, Vec3::new(1.0, 0.0, 1.0));
+        let end = translated(start, result.motion);
+        assert_eq!(result.blocked, Vec3::new(0, 0, 1));
+        assert_eq!(result.tiles, vec![corner]);
+        assert!((end.center().x - 2.0).abs() < 1e-4);
+        assert!(!inside(end, shape), "{:?}", end);
+    }
+
+    #[test]
+    fn stays_out_of_floor_it_starts_in() {
+        let mut area = Area::new(4, 3, 3);
+        for x in 0..4 {
+            area.set_tile(Vec3::new(x, 0, 1), Tile::Wall);
+        }
+
+        let sunk = body(Vec3::new(1.0, 0.3, 1.0));
+        let result =
+            sweep(&area, sunk, Vec3::new(0.0, -0.5, 0.0));
+        assert_eq!(result.blocked.y, -1);
+        assert_eq!(result.motion, Vec3::zero());
+
+        // Walking climbs back out onto the floor.
+        let mut aabb = sunk;
+        for _ in 0..10 {
+            let result = walk(
+                &area,
+                aabb,
+                Vec3::new(0.1, -0.02, 0.0),
+                0.5,
+            );
+            aabb = translated(aabb, result.motion);
+        }
+        assert!(
+            (aabb.min.y - 0.5).abs() < 1e-4,
+            "{:?}",
+            aabb
+        );
+    }
+    /// A floor with half blocks on it from `x` 3 on.
+    fn step_area() -> Area {
+        let mut area = Area::new(6, 3, 3);
+        for x in 0..6 {
+            for z in 0..3 {
+                area.set_tile(Vec3::new(x, 0, z), Tile::Wall);
+                if x >= 3 {
+                    area.set_tile(
+                        Vec3::new(x, 1, z),
+                        Tile::HalfBlock,
+                    );
+                }
+            }
+        }
+        area
+    }
+
+    /// Walks `steps` times by `motion`, checking the box
+    /// stays on the ground the whole way. The motion pulls
+    /// down a little like gravity, so landing shows up.
+    fn walk_on_ground(
+        area: &Area,
+        start: Aabb<f32>,
+        motion: Vec3<f32>,
+        steps: usize,
+    ) -> Aabb<f32> {
+        let mut aabb = start;
+        for _ in 0..steps {
+            let result = walk(area, aabb, motion, 0.5);
+            assert_eq!(result.blocked.y, -1, "{:?}", aabb);
+            aabb = translated(aabb, result.motion);
+        }
+        aabb
+    }
+
+    #[test]
+    fn walks_up_a_step() {
+        let area = step_area();
+        let end = walk_on_ground(
+            &area,
+            body(Vec3::new(1.0, 0.5, 1.0)),
+            Vec3::new(0.1, -0.02, 0.0),
+            30,
+        );
+        assert!((end.min.y - 1.0).abs() < 1e-4, "{:?}", end);
+        assert!((end.center().x - 4.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn blocked_by_a_wall() {
+        let mut area = step_area();
+        area.set_tile(Vec3::new(3, 1, 1), Tile::Wall);
+        let start = body(Vec3::new(1.0, 0.5, 1.0));
+        let result =
+            walk(&area, start, Vec3::new(2.0, 0.0, 0.0), 0.5);
+        assert_eq!(result.blocked.x, 1);
+        assert_eq!(result.tiles[0], Vec3::new(3, 1, 1));
+        let end = translated(start, result.motion);
+        assert!((end.max.x - 2.5).abs() < 1e-4, "{:?}", end);
+        assert!((end.min.y - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn stays_grounded_down_a_step() {
+        let area = step_area();
+        let end = walk_on_ground(
+            &area,
+            body(Vec3::new(4.0, 1.0, 1.0)),
+            Vec3::new(-0.1, -0.02, 0.0),
+            30,
+        );
+        assert!((end.min.y - 0.5).abs() < 1e-4, "{:?}", end);
+        assert!((end.center().x - 1.0).abs() < 1e-4);
+    }
+}
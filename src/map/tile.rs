@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use vek::{Aabb, Vec3};
+
+/// Direction a tile faces, stairs go up towards it.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Facing {
+    North,
+    East,
+    South,
+    West,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub enum Tile {
+    Air,
+    Wall,
+    HalfBlock,
+    Stairs(Facing),
+    Pillar,
+    Water,
+    Lava,
+    Grate,
+    Torch,
+    /// Takes the player to another level, see
+    /// `level::Levels`.
+    Exit,
+    /// A wall that breaks into rubble, see `durability`.
+    CrackedWall,
+    Crate,
+    /// What's left of a broken wall.
+    Rubble,
+}
+
+const fn aabb(min: [f32; 3], max: [f32; 3]) -> Aabb<f32> {
+    Aabb {
+        min: Vec3 {
+            x: min[0],
+            y: min[1],
+            z: min[2],
+        },
+        max: Vec3 {
+            x: max[0],
+            y: max[1],
+            z: max[2],
+        },
+    }
+}
+
+// Shapes are relative to the center of the tile, a full
+// tile goes from -0.5 to 0.5 on every axis.
+const FULL: [Aabb<f32>; 1] =
+    [aabb([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5])];
+
+const HALF: [Aabb<f32>; 1] =
+    [aabb([-0.5, -0.5, -0.5], [0.5, 0.0, 0.5])];
+
+const PILLAR: [Aabb<f32>; 1] =
+    [aabb([-0.25, -0.5, -0.25], [0.25, 0.5, 0.25])];
+
+const GRATE: [Aabb<f32>; 1] =
+    [aabb([-0.5, 0.4, -0.5], [0.5, 0.5, 0.5])];
+
+const TORCH: [Aabb<f32>; 1] =
+    [aabb([-0.08, -0.5, -0.08], [0.08, 0.1, 0.08])];
+
+const EXIT: [Aabb<f32>; 1] =
+    [aabb([-0.4, -0.5, -0.4], [0.4, -0.45, 0.4])];
+
+const CRATE: [Aabb<f32>; 1] =
+    [aabb([-0.4, -0.5, -0.4], [0.4, 0.3, 0.4])];
+
+const RUBBLE: [Aabb<f32>; 1] =
+    [aabb([-0.5, -0.5, -0.5], [0.5, -0.3, 0.5])];
+
+const LIQUID: [Aabb<f32>; 1] =
+    [aabb([-0.5, -0.5, -0.5], [0.5, 0.3, 0.5])];
+
+const STAIRS_NORTH: [Aabb<f32>; 4] = [
+    aabb([-0.5, -0.5, 0.25], [0.5, -0.25, 0.5]),
+    aabb([-0.5, -0.5, 0.0], [0.5, 0.0, 0.25]),
+    aabb([-0.5, -0.5, -0.25], [0.5, 0.25, 0.0]),
+    aabb([-0.5, -0.5, -0.5], [0.5, 0.5, -0.25]),
+];
+
+const STAIRS_EAST: [Aabb<f32>; 4] = [
+    aabb([-0.5, -0.5, -0.5], [-0.25, -0.25, 0.5]),
+    aabb([-0.25, -0.5, -0.5], [0.0, 0.0, 0.5]),
+    aabb([0.0, -0.5, -0.5], [0.25, 0.25, 0.5]),
+    aabb([0.25, -0.5, -0.5], [0.5, 0.5, 0.5]),
+];
+
+const STAIRS_SOUTH: [Aabb<f32>; 4] = [
+    aabb([-0.5, -0.5, -0.5], [0.5, -0.25, -0.25]),
+    aabb([-0.5, -0.5, -0.25], [0.5, 0.0, 0.0]),
+    aabb([-0.5, -0.5, 0.0], [0.5, 0.25, 0.25]),
+    aabb([-0.5, -0.5, 0.25], [0.5, 0.5, 0.5]),
+];
+
+const STAIRS_WEST: [Aabb<f32>; 4] = [
+    aabb([0.25, -0.5, -0.5], [0.5, -0.25, 0.5]),
+    aabb([0.0, -0.5, -0.5], [0.25, 0.0, 0.5]),
+    aabb([-0.25, -0.5, -0.5], [0.0, 0.25, 0.5]),
+    aabb([-0.5, -0.5, -0.5], [-0.25, 0.5, 0.5]),
+];
+
+impl Tile {
+    pub fn from_name(name: &str) -> Option<Tile> {
+        let tile = match name {
+            "air" => Tile::Air,
+            "wall" => Tile::Wall,
+            "half" => Tile::HalfBlock,
+            "stairs_n" => Tile::Stairs(Facing::North),
+            "stairs_e" => Tile::Stairs(Facing::East),
+            "stairs_s" => Tile::Stairs(Facing::South),
+            "stairs_w" => Tile::Stairs(Facing::West),
+            "pillar" => Tile::Pillar,
+            "water" => Tile::Water,
+            "lava" => Tile::Lava,
+            "grate" => Tile::Grate,
+            "torch" => Tile::Torch,
+            "exit" => Tile::Exit,
+            "cracked" => Tile::CrackedWall,
+            "crate" => Tile::Crate,
+            "rubble" => Tile::Rubble,
+            _ => return None,
+        };
+        Some(tile)
+    }
+
+    /// The name used in map legends, see `from_name`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tile::Air => "air",
+            Tile::Wall => "wall",
+            Tile::HalfBlock => "half",
+            Tile::Stairs(Facing::North) => "stairs_n",
+            Tile::Stairs(Facing::East) => "stairs_e",
+            Tile::Stairs(Facing::South) => "stairs_s",
+            Tile::Stairs(Facing::West) => "stairs_w",
+            Tile::Pillar => "pillar",
+            Tile::Water => "water",
+            Tile::Lava => "lava",
+            Tile::Grate => "grate",
+            Tile::Torch => "torch",
+            Tile::Exit => "exit",
+            Tile::CrackedWall => "cracked",
+            Tile::Crate => "crate",
+            Tile::Rubble => "rubble",
+        }
+    }
+
+    pub fn is_wall(&self) -> bool {
+        *self == Tile::Wall
+    }
+
+    /// Whether the tile hides what's behind it, partial
+    /// shapes can be seen over or around.
+    pub fn is_opaque(&self) -> bool {
+        self.is_wall() || *self == Tile::CrackedWall
+    }
+
+    pub fn is_solid(&self) -> bool {
+        !self.collision().is_empty()
+    }
+
+    pub fn is_liquid(&self) -> bool {
+        match self {
+            Tile::Water | Tile::Lava => true,
+            _ => false,
+        }
+    }
+
+    /// How much light the tile gives off, up to
+    /// `lighting::MAX_LIGHT`.
+    pub fn emission(&self) -> u8 {
+        match self {
+            Tile::Torch => 14,
+            Tile::Exit => 8,
+            Tile::Lava => 12,
+            _ => 0,
+        }
+    }
+
+    /// How much damage the tile takes before breaking, `None`
+    /// if it can't be broken.
+    pub fn durability(&self) -> Option<u16> {
+        match self {
+            Tile::CrackedWall => Some(30),
+            Tile::Crate => Some(10),
+            _ => None,
+        }
+    }
+
+    /// What the tile turns into once it breaks.
+    pub fn debris(&self) -> Tile {
+        match self {
+            Tile::CrackedWall => Tile::Rubble,
+            _ => Tile::Air,
+        }
+    }
+
+    /// The boxes an entity collides against, relative to the
+    /// center of the tile.
+    pub fn collision(&self) -> &'static [Aabb<f32>] {
+        match self {
+            Tile::Air |
+            Tile::Water |
+            Tile::Lava |
+            Tile::Torch |
+            Tile::Exit => &[],
+            Tile::Wall | Tile::CrackedWall => &FULL,
+            Tile::HalfBlock => &HALF,
+            Tile::Stairs(Facing::North) => &STAIRS_NORTH,
+            Tile::Stairs(Facing::East) => &STAIRS_EAST,
+            Tile::Stairs(Facing::South) => &STAIRS_SOUTH,
+            Tile::Stairs(Facing::West) => &STAIRS_WEST,
+            Tile::Pillar => &PILLAR,
+            Tile::Grate => &GRATE,
+            Tile::Crate => &CRATE,
+            Tile::Rubble => &RUBBLE,
+        }
+    }
+
+    /// The boxes that get drawn for the tile, same as the
+    /// collision except for liquids which can be waded
+    /// through and torches and exits which can be walked
+    /// over.
+    pub fn geometry(&self) -> &'static [Aabb<f32>] {
+        match self {
+            Tile::Water | Tile::Lava => &LIQUID,
+            Tile::Torch => &TORCH,
+            Tile::Exit => &EXIT,
+            _ => self.collision(),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use vek::Vec3;
+
+use super::{Area, Face, Facing, Marker, MarkerKind, Tile};
+
+/// The character each tile kind gets in the legend.
+fn symbol(tile: Tile) -> char {
+    match tile {
+        Tile::Air => '.',
+        Tile::Wall => '#',
+        Tile::HalfBlock => '_',
+        Tile::Stairs(Facing::North) => '^',
+        Tile::Stairs(Facing::East) => '>',
+        Tile::Stairs(Facing::South) => 'v',
+        Tile::Stairs(Facing::West) => '<',
+        Tile::Pillar => 'I',
+        Tile::Water => '~',
+        Tile::Lava => '%',
+        Tile::Grate => '=',
+        Tile::Torch => '*',
+        Tile::Exit => '@',
+        Tile::CrackedWall => '+',
+        Tile::Crate => 'x',
+        Tile::Rubble => ',',
+    }
+}
+
+/// Writes the area in the text format read by `parse`.
+/// Comments and the legend characters of the original file
+/// aren't kept.
+pub fn write(area: &Area) -> String {
+    let mut out = String::new();
+    let bounds = area.bounds();
+
+    writeln!(
+        out,
+        "size {} {} {}",
+        area.width(),
+        area.depth(),
+        area.height()
+    )
+    .unwrap();
+    if bounds.min != Vec3::zero() {
+        let min = bounds.min;
+        writeln!(out, "origin {} {} {}", min.x, min.y, min.z)
+            .unwrap();
+    }
+    if !area.markers().is_empty() {
+        out.push('\n');
+    }
+    for marker in area.markers() {
+        out.push_str(&marker_line(marker));
+        out.push('\n');
+    }
+
+    let mut tiles: Vec<Tile> = area
+        .iter()
+        .map(|(_, tile)| tile)
+        .chain(
+            area.materials().iter().map(|(tile, _, _)| tile),
+        )
+        .collect();
+    tiles.sort();
+    tiles.dedup();
+
+    out.push_str("\nlegend\n");
+    for tile in tiles {
+        write!(out, "{} {}", symbol(tile), tile.name())
+            .unwrap();
+        for face in &[Face::Side, Face::Top, Face::Bottom] {
+            if let Some(texture) =
+                area.materials().get(tile, *face)
+            {
+                write!(out, " {}={}", face.name(), texture)
+                    .unwrap();
+            }
+        }
+        out.push('\n');
+    }
+
+    let mut points = area.iter();
+    for _ in 0..area.height() {
+        out.push_str("\nlayer\n");
+        for _ in 0..area.depth() {
+            for _ in 0..area.width() {
+                let (_, tile) = points.next().unwrap();
+                out.push(symbol(tile));
+            }
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+fn marker_line(marker: &Marker) -> String {
+    let p = marker.position;
+    let position = format!("{} {} {}", p.x, p.y, p.z);
+    let facing = if marker.facing != 0.0 {
+        format!(" {}", marker.facing)
+    } else {
+        String::new()
+    };
+
+    match &marker.kind {
+        MarkerKind::PlayerStart => {
+            format!("player {}{}", position, facing)
+        }
+        MarkerKind::Enemy(kind) => {
+            format!("enemy {} {}{}", kind, position, facing)
+        }
+        MarkerKind::Item(kind) => {
+            format!("item {} {}", kind, position)
+        }
+        MarkerKind::Light { radius, color } => {
+            let mut line =
+                format!("light {} {}", position, radius);
+            if *color != (1.0, 1.0, 1.0) {
+                write!(
+                    line,
+                    " {} {} {}",
+                    color.0, color.1, color.2
+                )
+                .unwrap();
+            }
+            line
+        }
+        MarkerKind::Trigger { name, size } => format!(
+            "trigger {} {} {} {} {}",
+            name, position, size.0, size.1, size.2
+        ),
+    }
+}
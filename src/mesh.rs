@@ -1,14 +1,18 @@
 use gl;
 
 mod constant;
+mod terrain;
 mod vertex;
 
+pub use terrain::Terrain;
 use vertex::VertexData;
+pub use vertex::LIGHT_LOCATION;
 
 pub struct MeshBuilder {
     vertices: Vec<f32>,
     indices: Option<Vec<u32>>,
     texture: Option<Vec<f32>>,
+    light: Option<Vec<f32>>,
 }
 
 impl MeshBuilder {
@@ -17,6 +21,7 @@ impl MeshBuilder {
             vertices: Vec::new(),
             texture: None,
             indices: None,
+            light: None,
         }
     }
 
@@ -40,10 +45,18 @@ impl MeshBuilder {
         self
     }
 
+    /// Brightness of each vertex, from 0 to 1. Meshes without
+    /// it take the one given by `Draw::with_light`.
+    pub fn light(mut self, light: &[f32]) -> MeshBuilder {
+        self.light = Some(light.to_owned());
+        self
+    }
+
     pub fn finalize(self) -> Mesh {
         let verts = VertexData::new(
             self.vertices,
             self.texture,
+            self.light,
             self.indices,
         );
 
@@ -53,13 +66,18 @@ impl MeshBuilder {
 pub struct Mesh {
     vertex_data: VertexData,
     vao: u32,
+    buffers: [u32; 2],
 }
 
 impl Mesh {
     pub fn new(vertex_data: VertexData) -> Mesh {
-        let vao = vertex_data.setup_buffers();
+        let (vao, vbo, ebo) = vertex_data.setup_buffers();
 
-        Mesh { vertex_data, vao }
+        Mesh {
+            vertex_data,
+            vao,
+            buffers: [vbo, ebo],
+        }
     }
 
     pub fn build() -> MeshBuilder {
@@ -92,3 +110,12 @@ impl Mesh {
         }
     }
 }
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(2, self.buffers.as_ptr());
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use openglsdl::{
+    lighting::LightMap,
+    map::{Area, Face},
+};
+use vek::Vec3;
+
+use super::{constant, Mesh};
+
+/// The vertices of each face of the cube, with the way
+/// the face is looking.
+const FACES: [(Face, Range<usize>, (i32, i32, i32)); 6] = [
+    (Face::Side, 0..6, (0, 0, -1)),
+    (Face::Side, 6..12, (0, 0, 1)),
+    (Face::Side, 12..18, (-1, 0, 0)),
+    (Face::Side, 18..24, (1, 0, 0)),
+    (Face::Bottom, 24..30, (0, -1, 0)),
+    (Face::Top, 30..36, (0, 1, 0)),
+];
+
+/// The tiles of an area in one mesh for every face and
+/// material, lit at each vertex by the light around it.
+pub struct Terrain {
+    /// The meshes by the face they're for and the texture of
+    /// their material, `None` for the default one.
+    pub meshes: Vec<(Face, Option<String>, Mesh)>,
+    /// The `LightMap::revision` the meshes were lit with.
+    revision: u64,
+}
+
+#[derive(Default)]
+struct Group {
+    vertices: Vec<f32>,
+    texture: Vec<f32>,
+    light: Vec<f32>,
+}
+
+impl Terrain {
+    pub fn new(area: &Area, lights: &LightMap) -> Terrain {
+        let mut groups: BTreeMap<
+            (Face, Option<String>),
+            Group,
+        > = BTreeMap::new();
+        for (point, tile) in area.filled() {
+            let center = point.map(|e| e as f32);
+            for shape in tile.geometry() {
+                let size = Vec3::from(shape.size());
+                for (face, range, normal) in FACES.iter() {
+                    let material = area
+                        .materials()
+                        .get(tile, *face)
+                        .map(|path| path.to_string());
+                    let group = groups
+                        .entry((*face, material))
+                        .or_default();
+                    let normal = Vec3::from(*normal);
+                    for i in range.clone() {
+                        let vertex = Vec3::from_slice(
+                            &constant::CUBE_VERTICES
+                                [i * 3..i * 3 + 3],
+                        );
+                        let corner = center +
+                            shape.center() +
+                            vertex * size;
+                        group.vertices.extend_from_slice(
+                            &corner.into_array(),
+                        );
+                        group.texture.extend(
+                            &constant::CUBE_TEXTURE_MAPPING
+                                [i * 2..i * 2 + 2],
+                        );
+                        group.light.push(
+                            lights.corner_brightness(
+                                area, corner, normal,
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+
+        let meshes = groups
+            .into_iter()
+            .map(|((face, material), group)| {
+                let mesh = Mesh::build()
+                    .verts(&group.vertices)
+                    .texture_map(&group.texture)
+                    .light(&group.light)
+                    .finalize();
+                (face, material, mesh)
+            })
+            .collect();
+        Terrain {
+            meshes,
+            revision: lights.revision(),
+        }
+    }
+
+    /// Whether the light or the tiles changed since the
+    /// meshes were built.
+    pub fn is_stale(&self, lights: &LightMap) -> bool {
+        self.revision != lights.revision()
+    }
+}
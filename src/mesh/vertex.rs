@@ -1,16 +1,22 @@
 use gl::types::*;
 use std::ffi;
+
+/// Attribute location of the per vertex brightness.
+pub const LIGHT_LOCATION: u32 = 2;
+
 #[derive(Debug)]
 pub struct VertexData {
     data: Vec<f32>,
     indices: Option<Vec<u32>>,
     texture: Option<Vec<f32>>,
+    light: Option<Vec<f32>>,
 }
 
 impl VertexData {
     pub fn new(
         vertices: Vec<f32>,
         texture: Option<Vec<f32>>,
+        light: Option<Vec<f32>>,
         indices: Option<Vec<u32>>,
     ) -> VertexData {
         let data = vertices.chunks_exact(3).enumerate().fold(
@@ -23,6 +29,9 @@ impl VertexData {
                     acc.push(texture[i * 2]);
                     acc.push(texture[i * 2 + 1]);
                 }
+                if let Some(light) = light.as_ref() {
+                    acc.push(light[i]);
+                }
                 acc
             },
         );
@@ -30,6 +39,7 @@ impl VertexData {
             data,
             indices,
             texture,
+            light,
         }
     }
 
@@ -77,6 +87,9 @@ impl VertexData {
         if self.texture.is_some() {
             elements_per_vertex += 2;
         }
+        if self.light.is_some() {
+            elements_per_vertex += 1;
+        }
         elements_per_vertex
     }
 
@@ -94,7 +107,15 @@ impl VertexData {
         (3 * std::mem::size_of::<GLfloat>()) as *const _
     }
 
-    pub fn setup_buffers(&self) -> u32 {
+    pub fn light_offset(&self) -> *const ffi::c_void {
+        let offset =
+            if self.texture.is_some() { 5 } else { 3 };
+        (offset * std::mem::size_of::<GLfloat>()) as *const _
+    }
+
+    /// Returns the vertex array and the buffers it uses, the
+    /// element buffer is 0 without indices.
+    pub fn setup_buffers(&self) -> (u32, u32, u32) {
         unsafe {
             let mut vao = 0;
 
@@ -112,8 +133,8 @@ impl VertexData {
                 gl::STATIC_DRAW,
             );
 
+            let mut ebo = 0;
             if let Some(ref indices) = self.indices {
-                let mut ebo = 0;
                 gl::GenBuffers(1, &mut ebo);
                 gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
                 gl::BufferData(
@@ -126,11 +147,14 @@ impl VertexData {
 
             self.setup_position_attribute();
             self.setup_texture_attribute();
+            if self.light.is_some() {
+                self.setup_light_attribute();
+            }
 
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
 
-            vao
+            (vao, vbo, ebo)
         }
     }
 
@@ -163,4 +187,19 @@ impl VertexData {
             gl::EnableVertexAttribArray(1);
         }
     }
+
+    pub fn setup_light_attribute(&self) {
+        unsafe {
+            gl::VertexAttribPointer(
+                LIGHT_LOCATION,
+                1,
+                gl::FLOAT,
+                gl::FALSE,
+                self.stride(),
+                self.light_offset(),
+            );
+
+            gl::EnableVertexAttribArray(LIGHT_LOCATION);
+        }
+    }
 }
@@ -0,0 +1,684 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use vek::{Aabb, Vec3};
+
+use crate::map::{Area, Facing, Tile};
+
+const EPSILON: f32 = 0.01;
+
+/// Size and climbing limits of whoever is walking the path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Agent {
+    /// Half the width of the agent on x and z.
+    pub radius: f32,
+    pub height: f32,
+    /// Tallest ledge the agent can walk up without jumping.
+    pub step_height: f32,
+    /// Tallest ledge the agent can jump onto, zero if it
+    /// doesn't jump.
+    pub jump_height: f32,
+    /// Deepest drop the agent is willing to walk off, can be
+    /// infinite.
+    pub max_drop: f32,
+}
+
+impl Agent {
+    /// An agent `width` wide and `height` tall that walks up
+    /// ledges as tall as `step_height` and drops three times
+    /// its height.
+    pub fn new(
+        width: f32,
+        height: f32,
+        step_height: f32,
+    ) -> Agent {
+        Agent {
+            radius: width * 0.5,
+            height,
+            step_height,
+            jump_height: 0.0,
+            max_drop: height * 3.0,
+        }
+    }
+
+    /// The same agent, able to jump `height` high.
+    pub fn jumping(self, height: f32) -> Agent {
+        Agent {
+            jump_height: height,
+            ..self
+        }
+    }
+
+    /// Tallest ledge the agent gets onto one way or another.
+    fn climb_height(&self) -> f32 {
+        self.step_height.max(self.jump_height)
+    }
+}
+
+/// Where the feet of an agent standing in `cell` rest, `None`
+/// if the agent can't stand there.
+///
+/// A cell is walkable when its air sits over solid ground,
+/// either the full height tile below it or a lower shape in
+/// the cell itself like a half block. Stairs are walked at
+/// their middle height. Nothing covers the top of the area,
+/// so the cells right over it can be stood in too.
+pub fn standing(
+    area: &Area,
+    agent: &Agent,
+    cell: Vec3<i32>,
+) -> Option<f32> {
+    let tile = tile_or_roof(area, cell)?;
+    let center = cell.map(|e| e as f32);
+    let feet = match tile {
+        Tile::Lava => return None,
+        Tile::Stairs(_) => {
+            let top = center + Vec3::new(0.0, 0.5, 0.0);
+            return if fits(area, agent, top, 0.0) {
+                Some(center.y)
+            } else {
+                None
+            };
+        }
+        _ => match top(tile) {
+            Some(top) if top < 0.5 - EPSILON => {
+                center.y + top
+            }
+            Some(_) => return None,
+            None => {
+                let below =
+                    area.tile_at(cell - Vec3::unit_y())?;
+                match (below, top(below)) {
+                    (Tile::Stairs(_), _) => return None,
+                    (_, Some(top)) if top > 0.5 - EPSILON => {
+                        center.y - 0.5
+                    }
+                    _ => return None,
+                }
+            }
+        },
+    };
+
+    if fits(
+        area,
+        agent,
+        Vec3::new(center.x, feet, center.z),
+        0.0,
+    ) {
+        Some(feet)
+    } else {
+        None
+    }
+}
+
+/// The tile at `cell`, air for the cells over the top layer.
+fn tile_or_roof(
+    area: &Area,
+    cell: Vec3<i32>,
+) -> Option<Tile> {
+    let bounds = area.bounds();
+    let over = cell.y == bounds.max.y &&
+        area.tile_at(cell - Vec3::unit_y()).is_some();
+    if over {
+        Some(Tile::Air)
+    } else {
+        area.tile_at(cell)
+    }
+}
+
+/// The cell feet at `feet` stand in, the other way around
+/// from `standing`: from the bottom of the cell on the tile
+/// below up to just under the top of the cell.
+fn feet_cell(feet: Vec3<f32>) -> Vec3<i32> {
+    Vec3::new(
+        feet.x.round() as i32,
+        (feet.y + 0.5 + EPSILON).floor() as i32,
+        feet.z.round() as i32,
+    )
+}
+
+/// Highest point of the tile's collision, relative to its
+/// center.
+fn top(tile: Tile) -> Option<f32> {
+    tile.collision()
+        .iter()
+        .map(|shape| shape.max.y)
+        .fold(None, |top, y| {
+            Some(top.map_or(y, |t: f32| t.max(y)))
+        })
+}
+
+/// Whether the agent fits standing at `feet`, with `extra`
+/// headroom on top of its own height.
+fn fits(
+    area: &Area,
+    agent: &Agent,
+    feet: Vec3<f32>,
+    extra: f32,
+) -> bool {
+    let body = Aabb {
+        min: Vec3::new(
+            feet.x - agent.radius,
+            feet.y + EPSILON,
+            feet.z - agent.radius,
+        ),
+        max: Vec3::new(
+            feet.x + agent.radius,
+            feet.y + agent.height + extra,
+            feet.z + agent.radius,
+        ),
+    };
+
+    let range = body.map(|e| e.round() as i32);
+    for y in range.min.y..=range.max.y {
+        for z in range.min.z..=range.max.z {
+            for x in range.min.x..=range.max.x {
+                let cell = Vec3::new(x, y, z);
+                let tile = match area.tile_at(cell) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let center = cell.map(|e| e as f32);
+                for shape in tile.collision() {
+                    let min = center + shape.min;
+                    let max = center + shape.max;
+                    if body.min.x < max.x &&
+                        body.max.x > min.x &&
+                        body.min.y < max.y &&
+                        body.max.y > min.y &&
+                        body.min.z < max.z &&
+                        body.max.z > min.z
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+    true
+}
+
+fn facing_vector(facing: Facing) -> Vec3<i32> {
+    match facing {
+        Facing::North => Vec3::new(0, 0, -1),
+        Facing::East => Vec3::new(1, 0, 0),
+        Facing::South => Vec3::new(0, 0, 1),
+        Facing::West => Vec3::new(-1, 0, 0),
+    }
+}
+
+/// Height of the feet when leaving `cell` towards `dir`, or
+/// when coming into it if `dir` is reversed. Stairs can only
+/// be walked along their facing, at their low or high end.
+fn edge_height(
+    area: &Area,
+    cell: Vec3<i32>,
+    feet: f32,
+    dir: Vec3<i32>,
+) -> Option<f32> {
+    match area.tile_at(cell) {
+        Some(Tile::Stairs(facing)) => {
+            let up = facing_vector(facing);
+            if dir == up {
+                Some(cell.y as f32 + 0.5)
+            } else if dir == -up {
+                Some(cell.y as f32 - 0.5)
+            } else {
+                None
+            }
+        }
+        _ => Some(feet),
+    }
+}
+
+const DIRECTIONS: [(i32, i32); 4] =
+    [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+pub(crate) fn neighbours(
+    area: &Area,
+    agent: &Agent,
+    cell: Vec3<i32>,
+    feet: f32,
+) -> Vec<(Vec3<i32>, f32)> {
+    let mut found = Vec::new();
+    let drop = agent.max_drop.min(area.height() as f32).ceil()
+        as i32;
+    for &(dx, dz) in &DIRECTIONS {
+        let dir = Vec3::new(dx, 0, dz);
+        let leaving = match edge_height(area, cell, feet, dir)
+        {
+            Some(height) => height,
+            None => continue,
+        };
+
+        for dy in (-drop - 1..=1).rev() {
+            let next = cell + dir + Vec3::new(0, dy, 0);
+            let next_feet = match standing(area, agent, next)
+            {
+                Some(feet) => feet,
+                None => continue,
+            };
+            let entering = match edge_height(
+                area, next, next_feet, -dir,
+            ) {
+                Some(height) => height,
+                None => continue,
+            };
+
+            let rise = entering - leaving;
+            if rise > agent.climb_height() + EPSILON ||
+                -rise > agent.max_drop + EPSILON
+            {
+                continue;
+            }
+
+            // Climbing needs headroom over where the agent
+            // is, dropping needs the way down to be clear.
+            let clear = if rise > EPSILON {
+                let here = cell.map(|e| e as f32);
+                fits(
+                    area,
+                    agent,
+                    Vec3::new(here.x, leaving, here.z),
+                    rise,
+                )
+            } else if rise < -EPSILON {
+                let there = next.map(|e| e as f32);
+                let floor = match area.tile_at(next) {
+                    Some(Tile::Stairs(_)) => there.y + 0.5,
+                    _ => entering,
+                };
+                fits(
+                    area,
+                    agent,
+                    Vec3::new(there.x, floor, there.z),
+                    (leaving - floor).max(0.0),
+                )
+            } else {
+                true
+            };
+            if clear {
+                found.push((next, next_feet));
+                break;
+            }
+        }
+    }
+    found
+}
+
+#[derive(PartialEq)]
+struct Open {
+    estimate: f32,
+    cell: Vec3<i32>,
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Open) -> Ordering {
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn heuristic(a: Vec3<i32>, b: Vec3<i32>) -> f32 {
+    ((a.x - b.x).abs() + (a.z - b.z).abs()) as f32 +
+        ((a.y - b.y).abs() as f32 - 1.0).max(0.0)
+}
+
+/// A* from the `start` cell to the `goal` cell. Returns the
+/// feet positions to walk through, with the waypoints that
+/// are in a straight line of each other dropped.
+pub fn find_path(
+    area: &Area,
+    agent: &Agent,
+    start: Vec3<i32>,
+    goal: Vec3<i32>,
+) -> Option<Vec<Vec3<f32>>> {
+    let start_feet = standing(area, agent, start)?;
+    standing(area, agent, goal)?;
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Vec3<i32>, Vec3<i32>> =
+        HashMap::new();
+    let mut cost: HashMap<Vec3<i32>, (f32, f32)> =
+        HashMap::new();
+
+    cost.insert(start, (0.0, start_feet));
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        cell: start,
+    });
+
+    while let Some(Open { cell, .. }) = open.pop() {
+        if cell == goal {
+            let mut cells = vec![goal];
+            while let Some(previous) =
+                came_from.get(cells.last().unwrap())
+            {
+                cells.push(*previous);
+            }
+            cells.reverse();
+
+            let path = cells
+                .into_iter()
+                .map(|cell| {
+                    Vec3::new(
+                        cell.x as f32,
+                        cost[&cell].1,
+                        cell.z as f32,
+                    )
+                })
+                .collect();
+            return Some(smooth(area, agent, path));
+        }
+
+        let (so_far, feet) = cost[&cell];
+        for (next, next_feet) in
+            neighbours(area, agent, cell, feet)
+        {
+            let step = 1.0 + (next_feet - feet).abs();
+            let next_cost = so_far + step;
+            let better =
+                cost.get(&next).map_or(true, |(known, _)| {
+                    next_cost < *known
+                });
+            if better {
+                cost.insert(next, (next_cost, next_feet));
+                came_from.insert(next, cell);
+                open.push(Open {
+                    estimate: next_cost +
+                        heuristic(next, goal),
+                    cell: next,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+/// Drops waypoints that can be skipped by walking straight
+/// from the previous kept one to the next.
+fn smooth(
+    area: &Area,
+    agent: &Agent,
+    path: Vec<Vec3<f32>>,
+) -> Vec<Vec3<f32>> {
+    if path.len() < 3 {
+        return path;
+    }
+
+    let mut smoothed = vec![path[0]];
+    let mut from = 0;
+    for i in 1..path.len() - 1 {
+        if !walkable_line(
+            area,
+            agent,
+            path[from],
+            path[i + 1],
+        ) {
+            smoothed.push(path[i]);
+            from = i;
+        }
+    }
+    smoothed.push(path[path.len() - 1]);
+    smoothed
+}
+
+/// Whether the agent can walk in a straight line between two
+/// points on the same level ground.
+fn walkable_line(
+    area: &Area,
+    agent: &Agent,
+    from: Vec3<f32>,
+    to: Vec3<f32>,
+) -> bool {
+    if (from.y - to.y).abs() > EPSILON {
+        return false;
+    }
+
+    let length = from.distance(to);
+    let samples = (length / 0.25).ceil() as usize;
+    (0..=samples).all(|i| {
+        let at =
+            Vec3::lerp(from, to, i as f32 / samples as f32);
+        let cell = feet_cell(at);
+        match area.tile_at(cell) {
+            Some(Tile::Stairs(_)) | None => false,
+            Some(_) => {
+                standing(area, agent, cell)
+                    .map_or(false, |feet| {
+                        (feet - at.y).abs() < EPSILON
+                    }) &&
+                    fits(area, agent, at, 0.0)
+            }
+        }
+    })
+}
+
+/// Remembers the paths already found for one kind of agent.
+pub struct PathCache {
+    agent: Agent,
+    paths: HashMap<
+        (Vec3<i32>, Vec3<i32>),
+        Option<Vec<Vec3<f32>>>,
+    >,
+}
+
+impl PathCache {
+    pub fn new(agent: Agent) -> PathCache {
+        PathCache {
+            agent,
+            paths: HashMap::new(),
+        }
+    }
+
+    pub fn find(
+        &mut self,
+        area: &Area,
+        start: Vec3<i32>,
+        goal: Vec3<i32>,
+    ) -> Option<&Vec<Vec3<f32>>> {
+        let agent = self.agent;
+        self.paths
+            .entry((start, goal))
+            .or_insert_with(|| {
+                find_path(area, &agent, start, goal)
+            })
+            .as_ref()
+    }
+
+    /// Forgets the paths that could be affected by a change
+    /// to the tile at `point`: the ones walking by it,
+    /// and every failed search since the change could
+    /// open a way.
+    pub fn invalidate(&mut self, point: Vec3<i32>) {
+        let p = point.map(|e| e as f32);
+        self.paths.retain(|_, path| match path {
+            Some(path) => !path.windows(2).any(|segment| {
+                let (a, b): (Vec3<f32>, Vec3<f32>) =
+                    (segment[0], segment[1]);
+                // The tiles the agent walks through plus the
+                // ground under it and the walls around it.
+                p.x >= a.x.min(b.x) - 1.5 &&
+                    p.x <= a.x.max(b.x) + 1.5 &&
+                    p.z >= a.z.min(b.z) - 1.5 &&
+                    p.z <= a.z.max(b.z) + 1.5 &&
+                    p.y >= a.y.min(b.y) - 1.5 &&
+                    p.y <= a.y.max(b.y) + 2.5
+            }),
+            None => false,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.paths.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The size of the player, without jumping.
+    fn agent() -> Agent {
+        Agent::new(0.5, 0.5, 0.5)
+    }
+
+    #[test]
+    fn feet_cells() {
+        let area = {
+            let mut area = Area::new(3, 3, 3);
+            area.set_tile(Vec3::new(0, 0, 0), Tile::Wall);
+            area.set_tile(
+                Vec3::new(1, 1, 0),
+                Tile::HalfBlock,
+            );
+            area
+        };
+        for &cell in &[Vec3::new(0, 1, 0), Vec3::new(1, 1, 0)]
+        {
+            let feet =
+                standing(&area, &agent(), cell).unwrap();
+            let at = Vec3::new(cell.x as f32, feet, 0.0);
+            assert_eq!(feet_cell(at), cell);
+        }
+    }
+
+    #[test]
+    fn straight_over_half_blocks() {
+        let mut area = Area::new(6, 3, 3);
+        for x in 0..6 {
+            for z in 0..3 {
+                area.set_tile(Vec3::new(x, 0, z), Tile::Wall);
+                area.set_tile(
+                    Vec3::new(x, 1, z),
+                    Tile::HalfBlock,
+                );
+            }
+        }
+        let from = Vec3::new(0.0, 1.0, 0.0);
+        let to = Vec3::new(5.0, 1.0, 2.0);
+        assert!(walkable_line(&area, &agent(), from, to));
+
+        let path = find_path(
+            &area,
+            &agent(),
+            Vec3::new(0, 1, 0),
+            Vec3::new(5, 1, 2),
+        )
+        .unwrap();
+        assert_eq!(path, vec![from, to]);
+
+        area.set_tile(Vec3::new(3, 1, 1), Tile::Wall);
+        assert!(!walkable_line(&area, &agent(), from, to));
+    }
+    #[test]
+    fn climbs_stairs_and_steps() {
+        let mut area = Area::new(5, 1, 3);
+        for x in 0..5 {
+            area.set_tile(Vec3::new(x, 0, 0), Tile::Wall);
+        }
+        area.set_tile(Vec3::new(3, 1, 0), Tile::Wall);
+        area.set_tile(Vec3::new(4, 1, 0), Tile::Wall);
+        let (start, goal) =
+            (Vec3::new(0, 1, 0), Vec3::new(4, 2, 0));
+        let top = Vec3::new(4.0, 1.5, 0.0);
+
+        let steps =
+            [Tile::Stairs(Facing::East), Tile::HalfBlock];
+        for &step in &steps {
+            area.set_tile(Vec3::new(2, 1, 0), step);
+            let path =
+                find_path(&area, &agent(), start, goal)
+                    .unwrap();
+            assert_eq!(path.last(), Some(&top), "{:?}", step);
+        }
+
+        // A full block is too tall to walk up, not to jump.
+        area.set_tile(Vec3::new(2, 1, 0), Tile::Air);
+        assert!(
+            find_path(&area, &agent(), start, goal).is_none()
+        );
+        let jumper = agent().jumping(1.2);
+        assert!(
+            find_path(&area, &jumper, start, goal).is_some()
+        );
+
+        // Stairs only go along their facing.
+        area.set_tile(
+            Vec3::new(2, 1, 0),
+            Tile::Stairs(Facing::North),
+        );
+        assert!(
+            find_path(&area, &agent(), start, goal).is_none()
+        );
+    }
+
+    #[test]
+    fn drops_down_ledges() {
+        let mut area = Area::new(3, 1, 4);
+        for x in 0..3 {
+            area.set_tile(Vec3::new(x, 0, 0), Tile::Wall);
+        }
+        area.set_tile(Vec3::new(0, 1, 0), Tile::Wall);
+        area.set_tile(Vec3::new(0, 2, 0), Tile::Wall);
+        let (top, bottom) =
+            (Vec3::new(0, 3, 0), Vec3::new(2, 1, 0));
+
+        // Deeper than the agent is willing to drop.
+        assert!(
+            find_path(&area, &agent(), top, bottom).is_none()
+        );
+
+        let deeper = Agent {
+            max_drop: 2.0,
+            ..agent()
+        };
+        let path =
+            find_path(&area, &deeper, top, bottom).unwrap();
+        assert_eq!(
+            path.last(),
+            Some(&Vec3::new(2.0, 0.5, 0.0))
+        );
+        // But there's no way back up.
+        assert!(
+            find_path(&area, &deeper, bottom, top).is_none()
+        );
+    }
+
+    #[test]
+    fn cache_forgets_changed_paths() {
+        let mut area = Area::new(5, 1, 3);
+        for x in 0..5 {
+            area.set_tile(Vec3::new(x, 0, 0), Tile::Wall);
+        }
+        let (start, goal) =
+            (Vec3::new(0, 1, 0), Vec3::new(4, 1, 0));
+        let mut cache = PathCache::new(agent());
+        assert!(cache.find(&area, start, goal).is_some());
+
+        let wall = Vec3::new(2, 1, 0);
+        area.set_tile(wall, Tile::Wall);
+        // The old path stays until it's invalidated.
+        assert!(cache.find(&area, start, goal).is_some());
+        cache.invalidate(wall);
+        assert!(cache.find(&area, start, goal).is_none());
+
+        // Failed searches are tried again too.
+        area.set_tile(wall, Tile::Air);
+        cache.invalidate(wall);
+        assert!(cache.find(&area, start, goal).is_some());
+    }
+}
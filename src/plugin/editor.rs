@@ -0,0 +1,159 @@
+use std::path::PathBuf;
+
+use bevy::prelude::{
+    AppBuilder, IntoQuerySystem, Plugin, Query, Res, ResMut,
+};
+use sdl2::{keyboard::Keycode, mouse::MouseButton};
+use vek::Vec3;
+
+use crate::{
+    component::{Player, Position, Rotation},
+    input::Input,
+    map::{Area, Facing, RayHit, Tile},
+};
+
+/// Tiles the editor cycles through with the wheel.
+const PALETTE: [Tile; 15] = [
+    Tile::Wall,
+    Tile::HalfBlock,
+    Tile::Stairs(Facing::North),
+    Tile::Stairs(Facing::East),
+    Tile::Stairs(Facing::South),
+    Tile::Stairs(Facing::West),
+    Tile::Pillar,
+    Tile::Grate,
+    Tile::Water,
+    Tile::Lava,
+    Tile::Torch,
+    Tile::Exit,
+    Tile::CrackedWall,
+    Tile::Crate,
+    Tile::Rubble,
+];
+
+/// How far away tiles can be edited.
+const REACH: f32 = 8.0;
+
+const TOGGLE_KEY: Keycode = Keycode::Tab;
+const SAVE_KEY: Keycode = Keycode::F5;
+
+/// Block editor state. While enabled, the left button places
+/// the selected tile against the face under the crosshair,
+/// the right button removes the tile and the wheel picks
+/// which tile gets placed.
+pub struct Editor {
+    pub enabled: bool,
+    /// The file the level was loaded from, see `save_path`.
+    pub path: PathBuf,
+    selected: usize,
+    target: Option<RayHit>,
+}
+
+impl Editor {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Editor {
+        Editor {
+            enabled: false,
+            path: path.into(),
+            selected: 0,
+            target: None,
+        }
+    }
+
+    pub fn selected(&self) -> Tile {
+        PALETTE[self.selected]
+    }
+
+    /// Where the area gets saved, the extension picks the
+    /// format. Saving a hand-written `.map` would lose its
+    /// comments and legend, so it goes to a `.edited.map`
+    /// next to it instead.
+    pub fn save_path(&self) -> PathBuf {
+        let name = self.path.to_string_lossy();
+        if name.ends_with(".map") &&
+            !name.ends_with(".edited.map")
+        {
+            self.path.with_extension("edited.map")
+        } else {
+            self.path.clone()
+        }
+    }
+
+    /// The tile under the crosshair, if the editor is enabled
+    /// and it's within reach.
+    pub fn target(&self) -> Option<RayHit> {
+        if self.enabled {
+            self.target
+        } else {
+            None
+        }
+    }
+}
+
+pub struct EditorPlugin {
+    pub path: PathBuf,
+}
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(Editor::new(self.path.clone()))
+            .add_system(edit.system());
+    }
+}
+
+fn edit(
+    input: Res<Input>,
+    mut editor: ResMut<Editor>,
+    mut area: ResMut<Area>,
+    mut query: Query<(&Player, &Position, &Rotation)>,
+) {
+    if input.was_pressed(TOGGLE_KEY) {
+        editor.enabled = !editor.enabled;
+        println!(
+            "Editor {}",
+            if editor.enabled { "on" } else { "off" }
+        );
+    }
+    if !editor.enabled {
+        return;
+    }
+
+    if input.wheel() != 0 {
+        editor.selected = (editor.selected as i32 +
+            input.wheel())
+        .rem_euclid(PALETTE.len() as i32)
+            as usize;
+        println!("Placing {}", editor.selected().name());
+    }
+
+    editor.target = None;
+    for (_, position, rotation) in &mut query.iter() {
+        let forward =
+            rotation.quat.conjugate() * Vec3::forward_rh();
+        editor.target =
+            area.raycast(position.internal(), forward, REACH);
+    }
+
+    if let Some(hit) = editor.target {
+        if input.was_clicked(MouseButton::Left) &&
+            hit.normal != Vec3::zero()
+        {
+            let point = hit.tile + hit.normal;
+            if area
+                .tile_at(point)
+                .map_or(true, |t| t == Tile::Air)
+            {
+                area.set_tile(point, editor.selected());
+            }
+        } else if input.was_clicked(MouseButton::Right) {
+            area.set_tile(hit.tile, Tile::Air);
+        }
+    }
+
+    if input.was_pressed(SAVE_KEY) {
+        let path = editor.save_path();
+        match area.save(&path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(e) => println!("Couldn't save: {}", e),
+        }
+    }
+}
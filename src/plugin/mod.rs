@@ -1,18 +1,30 @@
 use bevy::{
-    app::DefaultTaskPoolOptions,
+    app::{stage, DefaultTaskPoolOptions},
     prelude::{
-        AppBuilder, Commands, IntoForEachSystem,
-        IntoQuerySystem, Mut, Plugin, Query, Res, ResMut,
-        Time,
+        AppBuilder, Commands, Entity, EventReader, Events,
+        IntoForEachSystem, IntoQuerySystem, Local, Mut,
+        Plugin, Query, Res, ResMut,
     },
 };
-use sdl2::keyboard::Keycode;
+use sdl2::{keyboard::Keycode, mouse::MouseButton};
 use vek::*;
 
-use crate::map;
+use crate::{
+    level::Levels,
+    lighting::LightMap,
+    map::{self, Damage, MarkerKind, Tile},
+};
+
+mod editor;
+
+pub use editor::{Editor, EditorPlugin};
 
 use crate::{
-    component::{BoundingBox, Position, Rotation, Velocity},
+    component::{
+        BoundingBox, Enemy, Grounded, Item, Jump,
+        LevelEntity, Light, Player, Position, Rotation,
+        Stepping, Trigger, Velocity,
+    },
     input::Input,
     util,
 };
@@ -33,46 +45,279 @@ impl Plugin for BasePlugin {
     }
 }
 
+/// Seconds the simulation moves forward on every update,
+/// however long frames take to draw.
+pub const TIMESTEP: f32 = 1.0 / 60.0;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(spawn.system())
+        app.add_event::<map::TileChanged>()
+            .add_event::<Damage>()
+            .add_startup_system(spawn.system())
+            .add_startup_system(track_tile_changes.system())
+            .add_system_to_stage(
+                stage::FIRST,
+                remember_positions.system(),
+            )
+            .add_system_to_stage(
+                stage::FIRST,
+                remember_rotations.system(),
+            )
             .add_system(gravity.system())
             .add_system(momentum.system())
+            .add_system(jump.system())
             .add_system(movement.system())
-            .add_system(rotation.system());
+            .add_system(rotation.system())
+            .add_system(travel.system())
+            .add_system(attack.system())
+            .add_system(damage_tiles.system())
+            .add_system_to_stage(
+                stage::POST_UPDATE,
+                send_tile_changes.system(),
+            )
+            .add_system_to_stage(
+                stage::POST_UPDATE,
+                update_lights.system(),
+            );
     }
 }
 
-fn spawn(mut commands: Commands) {
+/// Tallest ledge enemies walk up.
+const STEP_HEIGHT: f32 = 0.5;
+
+const JUMP_KEY: Keycode = Keycode::Space;
+
+/// Spawns the player and the entities for the markers in the
+/// area.
+fn spawn(mut commands: Commands, area: Res<map::Area>) {
+    let (start, yaw) = match area.player_start() {
+        Some(marker) => (marker.position, marker.yaw()),
+        None => (no_start(&area), 0.0),
+    };
     commands.spawn((
-        Position::new(1.5, 6.6, 1.5),
+        Player,
+        Position::from_vector(start),
         Velocity::new(),
-        Rotation::new(),
-        BoundingBox::new(0.15, 0.1),
+        Rotation::with_yaw(yaw),
+        Player::BOUNDING_BOX,
+        Player::STEPPING,
+        Grounded::new(),
+        Jump::new(Player::JUMP_HEIGHT),
     ));
+
+    spawn_markers(&mut commands, &area);
+}
+
+/// Where the player goes in an area without a player start.
+fn no_start(area: &map::Area) -> Vec3<f32> {
+    println!("Map has no player start");
+    area.bounds().map(|e| e as f32).center()
 }
 
-fn gravity(time: Res<Time>, mut velocity: Mut<Velocity>) {
-    velocity.apply_force(Vec3::down() * time.delta_seconds);
+/// Spawns the entities for every marker but the player
+/// start.
+fn spawn_markers(commands: &mut Commands, area: &map::Area) {
+    for marker in area.markers() {
+        let position = Position::from_vector(marker.position);
+        match &marker.kind {
+            MarkerKind::PlayerStart => {}
+            MarkerKind::Enemy(kind) => {
+                commands.spawn((
+                    LevelEntity,
+                    Enemy { kind: kind.clone() },
+                    position,
+                    Velocity::new(),
+                    Rotation::with_yaw(marker.yaw()),
+                    BoundingBox::new(0.5, 0.5, 0.35),
+                    Stepping {
+                        height: STEP_HEIGHT,
+                    },
+                    Grounded::new(),
+                ));
+            }
+            MarkerKind::Item(kind) => {
+                commands.spawn((
+                    LevelEntity,
+                    Item { kind: kind.clone() },
+                    position,
+                ));
+            }
+            MarkerKind::Light { radius, color } => {
+                commands.spawn((
+                    LevelEntity,
+                    Light {
+                        radius: *radius,
+                        color: Vec3::new(
+                            color.0, color.1, color.2,
+                        ),
+                    },
+                    position,
+                ));
+            }
+            MarkerKind::Trigger { name, size } => {
+                commands.spawn((
+                    LevelEntity,
+                    Trigger {
+                        name: name.clone(),
+                        size: Vec3::new(
+                            size.0, size.1, size.2,
+                        ),
+                    },
+                    position,
+                ));
+            }
+        }
+    }
 }
 
+fn track_tile_changes(mut area: ResMut<map::Area>) {
+    area.track_changes();
+}
+
+/// Sends whatever tiles changed this frame as `TileChanged`
+/// events.
+fn send_tile_changes(
+    mut area: ResMut<map::Area>,
+    mut events: ResMut<Events<map::TileChanged>>,
+) {
+    for change in area.take_changes() {
+        events.send(change);
+    }
+}
+
+/// Relights around the tiles that changed.
+fn update_lights(
+    mut reader: Local<EventReader<map::TileChanged>>,
+    events: Res<Events<map::TileChanged>>,
+    area: Res<map::Area>,
+    mut lights: ResMut<LightMap>,
+) {
+    for change in reader.iter(&events) {
+        lights.update(&area, change.point);
+    }
+}
+
+/// How far the player can hit tiles from.
+const ATTACK_REACH: f32 = 2.0;
+const ATTACK_DAMAGE: u16 = 10;
+
+/// Hits the tile in front of the player on a left click,
+/// unless the click is for the editor.
+fn attack(
+    input: Res<Input>,
+    editor: Res<Editor>,
+    mut damage: ResMut<Events<Damage>>,
+    _player: &Player,
+    position: &Position,
+    rotation: &Rotation,
+) {
+    if editor.enabled || !input.was_clicked(MouseButton::Left)
+    {
+        return;
+    }
+    damage.send(Damage::Ray {
+        origin: position.internal(),
+        direction: rotation.quat.conjugate() *
+            Vec3::forward_rh(),
+        reach: ATTACK_REACH,
+        amount: ATTACK_DAMAGE,
+    });
+}
+
+/// Applies the damage sent this frame, the tiles that break
+/// go out as `TileChanged` events like any other change.
+fn damage_tiles(
+    mut reader: Local<EventReader<Damage>>,
+    events: Res<Events<Damage>>,
+    mut area: ResMut<map::Area>,
+) {
+    for damage in reader.iter(&events) {
+        area.apply_damage(damage);
+    }
+}
+
+/// Swaps the area for the level an exit leads to when the
+/// player steps on one. The player keeps its velocity and
+/// rotation.
+fn travel(
+    mut commands: Commands,
+    mut levels: ResMut<Levels>,
+    mut area: ResMut<map::Area>,
+    mut lights: ResMut<LightMap>,
+    mut players: Query<(&Player, Mut<Position>)>,
+    mut spawned: Query<(Entity, &LevelEntity)>,
+) {
+    let mut exit = None;
+    for (_, position) in &mut players.iter() {
+        let cell =
+            position.internal().map(|e| e.round() as i32);
+        if area.tile_at(cell) == Some(Tile::Exit) {
+            exit = levels.exit_at(cell).cloned();
+        }
+    }
+    let exit = match exit {
+        Some(exit) => exit,
+        None => return,
+    };
+    let next = match levels.load_area(&exit.to) {
+        Ok(next) => next,
+        Err(e) => {
+            println!(
+                "Couldn't load level {}: {}",
+                exit.to, e
+            );
+            return;
+        }
+    };
+
+    for (entity, _) in &mut spawned.iter() {
+        commands.despawn(entity);
+    }
+    *area = next;
+    area.track_changes();
+    lights.rebuild(&area);
+    levels.enter(&exit.to);
+
+    let start = match (exit.spawn, area.player_start()) {
+        (Some(spawn), _) => Vec3::from(spawn),
+        (None, Some(marker)) => marker.position,
+        (None, None) => no_start(&area),
+    };
+    for (_, mut position) in &mut players.iter() {
+        *position = Position::from_vector(start);
+    }
+    spawn_markers(&mut commands, &area);
+}
+
+/// Keeps where everything was before this step moves it, to
+/// draw it in between.
+fn remember_positions(mut position: Mut<Position>) {
+    position.remember();
+}
+
+fn remember_rotations(mut rotation: Mut<Rotation>) {
+    rotation.remember();
+}
+
+fn gravity(mut velocity: Mut<Velocity>) {
+    velocity.apply_force(Vec3::down() * TIMESTEP);
+}
+
+/// Walks the player towards where it's looking, leaving the
+/// vertical velocity to gravity and jumps.
 fn momentum(
-    time: Res<Time>,
     input: Res<Input>,
+    _player: &Player,
     rotation: &Rotation,
     mut velocity: Mut<Velocity>,
 ) {
-    let dt = time.delta_seconds;
+    let dt = TIMESTEP;
 
     let mut applied_vel: Vec3<f32> = Vec3::zero();
     let conj = rotation.quat.conjugate();
 
-    if input.is_pressed(Keycode::Space) {
-        applied_vel += conj * Vec3::new(0.0, 1.0, 0.0);
-    }
-
     if input.is_pressed(Keycode::W) {
         applied_vel += conj * Vec3::forward_rh();
     }
@@ -91,104 +336,139 @@ fn momentum(
 
     let transition_speed = 6.0;
     let max_speed = 0.10;
+    applied_vel.y = 0.0;
     if applied_vel.magnitude().abs() >= 0.01 {
         applied_vel = applied_vel.normalized();
     }
 
     applied_vel *= max_speed;
-    *velocity = Velocity::from(
-        velocity.internal() * (1.0 - dt * transition_speed) +
-            applied_vel * (dt * transition_speed),
-    );
+    let current = velocity.internal();
+    let blended = current * (1.0 - dt * transition_speed) +
+        applied_vel * (dt * transition_speed);
+    *velocity = Velocity::from(Vec3::new(
+        blended.x, current.y, blended.z,
+    ));
+}
+
+/// Jumps if the jump key was pressed within the last
+/// `buffer_time` and the player is on the ground, or walked
+/// off it less than `coyote_time` ago. Letting go of the key
+/// on the way up cuts the jump short.
+fn jump(
+    input: Res<Input>,
+    _player: &Player,
+    grounded: &Grounded,
+    mut jump: Mut<Jump>,
+    mut velocity: Mut<Velocity>,
+) {
+    let dt = TIMESTEP;
+    let mut vel = velocity.internal();
+
+    let buffer_time = jump.buffer_time;
+    jump.buffered = if input.was_pressed(JUMP_KEY) {
+        Some(0.0)
+    } else {
+        jump.buffered
+            .map(|t| t + dt)
+            .filter(|t| *t <= buffer_time)
+    };
+
+    // Falling rather than going up, so a jump that just left
+    // the ground doesn't count as walking off it.
+    let can_jump = grounded.on_ground ||
+        (grounded.time_in_air <= jump.coyote_time &&
+            vel.y <= 0.0);
+    if can_jump && jump.buffered.is_some() {
+        // Gravity takes `TIMESTEP` off the velocity every
+        // step, so this is about the speed that stops at
+        // `jump.height`.
+        vel.y = (2.0 * TIMESTEP * jump.height).sqrt();
+        jump.buffered = None;
+        jump.rising = true;
+    } else if jump.rising {
+        if vel.y <= 0.0 {
+            jump.rising = false;
+        } else if !input.is_pressed(JUMP_KEY) {
+            vel.y *= jump.release_cut;
+            jump.rising = false;
+        }
+    }
+
+    *velocity = Velocity::from(vel);
 }
 
+/// Moves everything by its velocity. Entities with a
+/// `BoundingBox` stop at the tiles in the way and slide along
+/// them, the rest go right through. Those that are also
+/// `Stepping` walk up and down ledges, and `Grounded` ones
+/// find out whether they landed on something.
 fn movement(
-    time: Res<Time>,
     area: Res<map::Area>,
     mut colvec: ResMut<Vec<Vec3<i32>>>,
     mut query: Query<(
-        &Rotation,
         Mut<Velocity>,
         Mut<Position>,
         Option<&BoundingBox>,
+        Option<&Stepping>,
+        Option<Mut<Grounded>>,
     )>,
 ) {
-    let speed = 12.0;
-    let dt = time.delta_seconds;
-    let speed = dt * speed;
-
-    for (direction, mut velocity, mut position, bound_box) in
-        &mut query.iter()
+    for (
+        mut velocity,
+        mut position,
+        bounding_box,
+        stepping,
+        grounded,
+    ) in &mut query.iter()
     {
-        let movement_vector = velocity.internal();
-
-        let mut new_position =
-            position.move_towards(movement_vector).internal();
-
-        const MAX_ATTEMPTS: usize = 16;
-        let mut attempts = 0;
-        while let Some((collidee, collider, p)) =
-            area.blocks_around(new_position)
-        {
-            if attempts == MAX_ATTEMPTS {
-                break;
+        let bounding_box = match bounding_box {
+            Some(bounding_box) => bounding_box,
+            None => {
+                position.add(velocity.internal());
+                continue;
             }
+        };
 
-            colvec.push(p);
+        let aabb = bounding_box.at(position.internal());
+        let sweep = match stepping {
+            Some(stepping) => area.walk(
+                aabb,
+                velocity.internal(),
+                stepping.height,
+            ),
+            None => area.sweep(aabb, velocity.internal()),
+        };
+        colvec.extend(sweep.tiles);
 
-            let pen =
-                collider.collision_vector_with_aabb(collidee);
-            let max_axis =
-                pen.map(|e| e.abs()).reduce_partial_min();
-            let mut dir = -pen.map(|e| {
-                if e.abs().to_bits() == max_axis.to_bits() {
+        if let Some(mut grounded) = grounded {
+            grounded.on_ground = sweep.blocked.y == -1;
+            if grounded.on_ground {
+                grounded.time_in_air = 0.0;
+            } else {
+                grounded.time_in_air += TIMESTEP;
+            }
+        }
+
+        position.add(sweep.motion);
+        *velocity = Velocity::from(velocity.internal().map2(
+            sweep.blocked,
+            |e, b| {
+                if b == 0 {
                     e
                 } else {
                     0.0
                 }
-            });
-
-            if (dir.x.abs() > 0.0 || dir.z.abs() > 0.0) &&
-                pen.y.abs() < 0.05
-            {
-                dir = Vec3::new(-dir.x, 0.0, -dir.z);
-            } else {
-                *velocity = Velocity::from(
-                    velocity.internal().map2(dir, |e, d| {
-                        if d * e.signum() < 0.0 {
-                            0.0
-                        } else {
-                            e
-                        }
-                    }),
-                );
-            }
-            if attempts == 0 {
-                new_position = new_position + dir;
-            } else {
-                new_position += dir;
-            }
-
-            attempts += 1;
-        }
-        *position = Position::from_vector(new_position);
+            },
+        ));
     }
 }
 
-fn mul_each(vec: Vec3<f32>, other: Vec3<f32>) -> Vec3<f32> {
-    Vec3::new(
-        vec.x * other.x,
-        vec.y * other.y,
-        vec.z * other.z,
-    )
-}
-
 fn rotation(
-    time: Res<Time>,
     input: Res<Input>,
+    _player: &Player,
     mut direction: Mut<Rotation>,
 ) {
-    let dt = time.delta_seconds;
+    let dt = TIMESTEP;
     let speed = 120.0;
     let speed = dt * speed;
 
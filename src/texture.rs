@@ -69,3 +69,11 @@ impl Texture {
         Texture { width, height, id }
     }
 }
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
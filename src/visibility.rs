@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+
+use vek::Vec3;
+
+use crate::map::Area;
+
+const EPSILON: f32 = 0.001;
+
+/// Transforms from the first octant to each of the eight,
+/// as `(xx, xz, zx, zz)`.
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// Tiles that can be seen from `eye`, up to `radius` tiles
+/// away.
+///
+/// Only looks around the floor the eye is on, with recursive
+/// shadowcasting. With `vertical` the floors above and below
+/// are also checked, keeping the tiles there's a clear line
+/// to.
+pub fn visible_tiles(
+    area: &Area,
+    eye: Vec3<f32>,
+    radius: f32,
+    vertical: bool,
+) -> HashSet<Vec3<i32>> {
+    let center = eye.map(|e| e.round() as i32);
+    let reach = radius.ceil() as i32;
+    let mut visible = HashSet::new();
+    if area.contains(center) {
+        visible.insert(center);
+    }
+
+    for octant in &OCTANTS {
+        cast(
+            area,
+            center,
+            radius,
+            1,
+            1.0,
+            0.0,
+            *octant,
+            &mut visible,
+        );
+    }
+
+    if vertical {
+        let bounds = area.bounds();
+        let floors = (center.y - reach).max(bounds.min.y)..
+            (center.y + reach + 1).min(bounds.max.y);
+        for y in floors.filter(|y| *y != center.y) {
+            for z in center.z - reach..=center.z + reach {
+                for x in center.x - reach..=center.x + reach {
+                    let cell = Vec3::new(x, y, z);
+                    let offset =
+                        (cell - center).map(|e| e as f32);
+                    if offset.magnitude_squared() <=
+                        radius * radius &&
+                        area.contains(cell) &&
+                        clear_line(area, eye, cell)
+                    {
+                        visible.insert(cell);
+                    }
+                }
+            }
+        }
+    }
+
+    visible
+}
+
+/// Lights one octant row by row, `start` and `end` are the
+/// slopes still in view.
+#[allow(clippy::too_many_arguments)]
+fn cast(
+    area: &Area,
+    center: Vec3<i32>,
+    radius: f32,
+    row: i32,
+    mut start: f32,
+    end: f32,
+    (xx, xz, zx, zz): (i32, i32, i32, i32),
+    visible: &mut HashSet<Vec3<i32>>,
+) {
+    if start < end {
+        return;
+    }
+    let reach = radius.ceil() as i32;
+    let mut next_start = start;
+
+    for distance in row..=reach {
+        let dz = -distance;
+        let mut blocked = false;
+        for dx in -distance..=0 {
+            let left = (dx as f32 - 0.5) / (dz as f32 + 0.5);
+            let right = (dx as f32 + 0.5) / (dz as f32 - 0.5);
+            if start < right {
+                continue;
+            } else if end > left {
+                break;
+            }
+
+            let cell = center +
+                Vec3::new(
+                    dx * xx + dz * xz,
+                    0,
+                    dx * zx + dz * zz,
+                );
+            if ((dx * dx + dz * dz) as f32) <= radius * radius &&
+                area.contains(cell)
+            {
+                visible.insert(cell);
+            }
+
+            let opaque = area
+                .tile_at(cell)
+                .map_or(false, |t| t.is_opaque());
+            if blocked {
+                if opaque {
+                    next_start = right;
+                } else {
+                    blocked = false;
+                    start = next_start;
+                }
+            } else if opaque && distance < reach {
+                blocked = true;
+                cast(
+                    area,
+                    center,
+                    radius,
+                    distance + 1,
+                    start,
+                    left,
+                    (xx, xz, zx, zz),
+                    visible,
+                );
+                next_start = right;
+            }
+        }
+        if blocked {
+            break;
+        }
+    }
+}
+
+/// Walks the tiles between `eye` and the closest point of
+/// `target`, true if none of them hide it.
+fn clear_line(
+    area: &Area,
+    eye: Vec3<f32>,
+    target: Vec3<i32>,
+) -> bool {
+    // Aiming at the closest point rather than the center, a
+    // floor only shows its top face.
+    let closest = eye.map2(target, |e, t| {
+        let t = t as f32;
+        e.max(t - 0.5).min(t + 0.5)
+    });
+    let shifted = eye + 0.5;
+    let length = (closest - eye).magnitude();
+    let dir = match (closest - eye).try_normalized() {
+        Some(dir) => dir,
+        None => return true,
+    };
+
+    let mut cell = shifted.map(|e| e.floor() as i32);
+    let step = dir.map(|e| e.signum() as i32);
+    let t_delta = dir.map(|e| {
+        if e != 0.0 {
+            1.0 / e.abs()
+        } else {
+            std::f32::INFINITY
+        }
+    });
+    let mut t_max = Vec3::new(0, 1, 2).map(|axis| {
+        let (d, p, c) =
+            (dir[axis], shifted[axis], cell[axis]);
+        if d > 0.0 {
+            (c as f32 + 1.0 - p) / d
+        } else if d < 0.0 {
+            (p - c as f32) / -d
+        } else {
+            std::f32::INFINITY
+        }
+    });
+
+    while cell != target {
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        // Reaching the target face is enough, whatever the
+        // line touches right on it doesn't hide anything.
+        if t_max[axis] >= length - EPSILON {
+            return true;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        if cell != target &&
+            area.tile_at(cell)
+                .map_or(false, |t| t.is_opaque())
+        {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Tile;
+
+    /// A room with nothing in it, the eye in the middle.
+    fn room() -> (Area, Vec3<f32>) {
+        (Area::new(11, 11, 1), Vec3::new(5.0, 0.0, 5.0))
+    }
+
+    #[test]
+    fn walls_cast_shadows() {
+        let (mut area, eye) = room();
+        area.set_tile(Vec3::new(5, 0, 3), Tile::Wall);
+
+        let visible = visible_tiles(&area, eye, 5.0, false);
+        assert!(visible.contains(&Vec3::new(5, 0, 3)));
+        assert!(!visible.contains(&Vec3::new(5, 0, 2)));
+        assert!(!visible.contains(&Vec3::new(5, 0, 1)));
+        assert!(visible.contains(&Vec3::new(5, 0, 7)));
+    }
+
+    #[test]
+    fn stops_at_radius() {
+        let (area, eye) = room();
+        let visible = visible_tiles(&area, eye, 3.0, false);
+        assert!(visible.contains(&Vec3::new(5, 0, 8)));
+        assert!(!visible.contains(&Vec3::new(5, 0, 9)));
+        assert!(visible.contains(&Vec3::new(7, 0, 7)));
+        assert!(!visible.contains(&Vec3::new(8, 0, 7)));
+    }
+
+    #[test]
+    fn octants_are_symmetric() {
+        let (area, eye) = room();
+        let radius = 4.5;
+        let visible =
+            visible_tiles(&area, eye, radius, false);
+        let center = Vec3::new(5, 0, 5);
+        for dz in -5..=5 {
+            for dx in -5..=5 {
+                let cell = center + Vec3::new(dx, 0, dz);
+                let inside = ((dx * dx + dz * dz) as f32) <=
+                    radius * radius;
+                assert_eq!(
+                    visible.contains(&cell),
+                    inside,
+                    "{:?}",
+                    cell
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ceilings_hide_floors_below() {
+        let mut area = Area::new(5, 5, 4);
+        for z in 0..5 {
+            for x in 0..5 {
+                area.set_tile(Vec3::new(x, 0, z), Tile::Wall);
+                area.set_tile(Vec3::new(x, 2, z), Tile::Wall);
+            }
+        }
+        let eye = Vec3::new(2.0, 3.0, 2.0);
+        let floor = Vec3::new(2, 0, 2);
+
+        let visible = visible_tiles(&area, eye, 4.0, true);
+        assert!(visible.contains(&Vec3::new(2, 2, 2)));
+        assert!(!visible.contains(&Vec3::new(2, 1, 2)));
+        assert!(!visible.contains(&floor));
+        let flat = visible_tiles(&area, eye, 4.0, false);
+        assert!(flat.iter().all(|cell| cell.y == 3));
+
+        // Through a hole in the ceiling.
+        area.set_tile(Vec3::new(2, 2, 2), Tile::Air);
+        let visible = visible_tiles(&area, eye, 4.0, true);
+        assert!(visible.contains(&floor));
+    }
+}